@@ -0,0 +1,517 @@
+mod rules;
+mod state;
+mod webhook;
+
+pub use self::rules::{
+    render_template, AlertRuleConfig, Comparison, MetricPath, Severity, Threshold, DEFAULT_MESSAGE,
+};
+pub use self::state::{Alert, AlertState, RuleState, Timing};
+pub use self::webhook::{Payload, WebhookClient, WebhookConfig, WebhookNotifier};
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use log::{error, info, log, Level};
+use serde_json::Value;
+use crate::config::Config;
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, MemoryEvent, MemoryEventKind};
+use crate::sampler::SamplerHandle;
+
+// Called when a rule starts firing, repeats while firing, and resolves
+pub trait Notifier: Send {
+    fn notify(&self, alert: &Alert);
+}
+
+// Writes alerts to the application log at a level matching their severity
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn notify(&self, alert: &Alert) {
+        match alert.state {
+            AlertState::Resolved => info!("RESOLVED [{}] {}", alert.severity, alert.message),
+            _ => {
+                let level = match alert.severity {
+                    Severity::Info => Level::Info,
+                    Severity::Warning => Level::Warn,
+                    Severity::Critical => Level::Error,
+                };
+                log!(level, "ALERT [{}] {}", alert.severity, alert.message);
+            }
+        }
+    }
+}
+
+pub type Predicate = Box<dyn Fn(&DetailedMetrics) -> bool + Send>;
+
+pub enum Condition {
+    Custom(Predicate),
+    Threshold(Threshold),
+}
+
+pub enum Message {
+    Custom(Box<dyn Fn(&DetailedMetrics) -> String + Send>),
+    Template(String),
+}
+
+pub struct AlertRule {
+    pub name: String,
+    pub severity: Severity,
+    pub condition: Condition,
+    // For custom rules: once firing, the rule resolves only when this
+    // returns true rather than as soon as `condition` turns false
+    pub clear: Option<Predicate>,
+    pub message: Message,
+    pub timing: Timing,
+}
+
+impl AlertRule {
+    pub fn new<C, M>(name: &str, severity: Severity, condition: C, message: M) -> Self
+    where
+        C: Fn(&DetailedMetrics) -> bool + Send + 'static,
+        M: Fn(&DetailedMetrics) -> String + Send + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            severity,
+            condition: Condition::Custom(Box::new(condition)),
+            clear: None,
+            message: Message::Custom(Box::new(message)),
+            timing: Timing { for_duration: Duration::ZERO, repeat_interval: None },
+        }
+    }
+
+    pub fn with_for(mut self, duration: Duration) -> Self {
+        self.timing.for_duration = duration;
+        self
+    }
+
+    pub fn with_repeat_interval(mut self, interval: Duration) -> Self {
+        self.timing.repeat_interval = Some(interval);
+        self
+    }
+
+    pub fn with_clear<F>(mut self, clear: F) -> Self
+    where
+        F: Fn(&DetailedMetrics) -> bool + Send + 'static,
+    {
+        self.clear = Some(Box::new(clear));
+        self
+    }
+
+    pub fn from_config(config: &AlertRuleConfig) -> Result<Self, MonitorError> {
+        let percent_of = match &config.percent_of {
+            Some(path) => Some(MetricPath::parse(path)?),
+            None => None,
+        };
+        let threshold = Threshold {
+            metric: MetricPath::parse(&config.metric)?,
+            percent_of,
+            op: config.op,
+            threshold: config.threshold,
+            clear_threshold: config.clear_threshold,
+        };
+        threshold.validate().map_err(|e| MonitorError::Config(format!("Alert rule {:?}: {}", config.name, e)))?;
+        Ok(Self {
+            name: config.name.clone(),
+            severity: config.severity,
+            condition: Condition::Threshold(threshold),
+            clear: None,
+            message: Message::Template(config.message.clone().unwrap_or_else(|| DEFAULT_MESSAGE.to_string())),
+            timing: Timing {
+                for_duration: Duration::from_secs(config.for_secs),
+                repeat_interval: config.repeat_interval_secs.map(Duration::from_secs),
+            },
+        })
+    }
+
+    // Whether the condition holds, and the observed value for threshold
+    // rules. A firing rule is held against its clear condition.
+    fn evaluate(&self, metrics: &DetailedMetrics, doc: &mut LazyDocument, firing: bool) -> (bool, Option<f64>) {
+        match &self.condition {
+            Condition::Custom(condition) => match (&self.clear, firing) {
+                (Some(clear), true) => (!clear(metrics), None),
+                _ => (condition(metrics), None),
+            },
+            Condition::Threshold(threshold) => {
+                let doc = doc.get(metrics);
+                let active = if firing { threshold.evaluate_firing(doc) } else { threshold.evaluate(doc) };
+                (active.is_some(), threshold.value(doc))
+            }
+        }
+    }
+
+    fn render(&self, metrics: &DetailedMetrics, state: AlertState, value: Option<f64>) -> String {
+        match (&self.message, &self.condition, value) {
+            (Message::Custom(message), _, _) => message(metrics),
+            (Message::Template(template), Condition::Threshold(threshold), _) => {
+                render_template(template, &self.name, self.severity, state, threshold, value)
+            }
+            (Message::Template(template), _, _) => {
+                template.replace("{name}", &self.name).replace("{state}", &state.to_string())
+            }
+        }
+    }
+}
+
+// OOM kills are critical; limit events are a warning for memory.max, where
+// allocations start failing, and informational for memory.high throttling.
+// Suspected leaks are a warning.
+fn event_alert(event: &MemoryEvent, now: SystemTime) -> Alert {
+    let severity = match event.kind {
+        MemoryEventKind::OomKill => Severity::Critical,
+        MemoryEventKind::MemoryMax | MemoryEventKind::LeakSuspected => Severity::Warning,
+        MemoryEventKind::MemoryHigh => Severity::Info,
+    };
+    Alert {
+        rule: event.kind.to_string(),
+        severity,
+        state: AlertState::Firing,
+        message: event.message.clone(),
+        metric: None,
+        value: event.memory.map(|m| m as f64),
+        threshold: None,
+        active_since: event.timestamp,
+        timestamp: now,
+    }
+}
+
+// Threshold rules look values up in the JSON form of the sample; it is
+// built at most once per check, and only if such a rule exists.
+struct LazyDocument(Option<Value>);
+
+impl LazyDocument {
+    fn get(&mut self, metrics: &DetailedMetrics) -> &Value {
+        self.0.get_or_insert_with(|| serde_json::to_value(metrics).unwrap_or(Value::Null))
+    }
+}
+
+pub struct AlertManager {
+    rules: Vec<AlertRule>,
+    // Compiled from the config; replaced as a whole on reload
+    config_rules: Vec<AlertRule>,
+    notifiers: Vec<Box<dyn Notifier>>,
+    // One per configured webhook; replaced as a whole on reload
+    webhooks: Vec<Box<dyn Notifier>>,
+    // Keyed by rule name, so state survives a config reload
+    states: HashMap<String, RuleState>,
+}
+
+impl Default for AlertManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertManager {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            config_rules: Vec::new(),
+            notifiers: Vec::new(),
+            webhooks: Vec::new(),
+            states: HashMap::new(),
+        }
+    }
+
+    // Loads the configured rules and starts a notifier per webhook
+    pub fn from_config(config: &Config) -> Result<Self, MonitorError> {
+        let mut manager = Self::new();
+        manager.load_config(config)?;
+        Ok(manager)
+    }
+
+    pub fn load_config(&mut self, config: &Config) -> Result<(), MonitorError> {
+        self.load_rules(&config.alerts)?;
+        self.load_webhooks(&config.webhooks)
+    }
+
+    // Replace the webhook notifiers; the old workers exit once they have
+    // delivered what is already queued
+    pub fn load_webhooks(&mut self, configs: &[WebhookConfig]) -> Result<(), MonitorError> {
+        self.webhooks = configs
+            .iter()
+            .map(|config| Ok(Box::new(WebhookNotifier::new(config.clone())?) as Box<dyn Notifier>))
+            .collect::<Result<_, MonitorError>>()?;
+        Ok(())
+    }
+
+    pub fn add_rule(&mut self, rule: AlertRule) {
+        self.rules.push(rule);
+    }
+
+    // Compile and install the configured rules. If any rule is invalid, or
+    // names clash (state is kept by name), the previously loaded rules stay
+    // in effect.
+    pub fn load_rules(&mut self, configs: &[AlertRuleConfig]) -> Result<(), MonitorError> {
+        let mut seen: HashSet<&str> = self.rules.iter().map(|r| r.name.as_str()).collect();
+        if let Some(duplicate) = configs.iter().find(|c| !seen.insert(&c.name)) {
+            return Err(MonitorError::Config(format!("Duplicate alert rule name {:?}", duplicate.name)));
+        }
+        self.config_rules = configs
+            .iter()
+            .map(AlertRule::from_config)
+            .collect::<Result<_, _>>()?;
+        let names: Vec<&str> = self.rules.iter().chain(&self.config_rules).map(|r| r.name.as_str()).collect();
+        self.states.retain(|name, _| names.contains(&name.as_str()));
+        Ok(())
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len() + self.config_rules.len()
+    }
+
+    pub fn add_notifier(&mut self, notifier: Box<dyn Notifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    pub fn state(&self, rule: &str) -> Option<&RuleState> {
+        self.states.get(rule)
+    }
+
+    pub fn firing(&self) -> impl Iterator<Item = &str> {
+        self.states.iter().filter(|(_, s)| s.is_firing()).map(|(name, _)| name.as_str())
+    }
+
+    // Advance every rule's state with the sample, using its timestamp as
+    // the clock, and notify about firings, repeats and resolutions. The
+    // alerts that were sent are returned.
+    pub fn check_alerts(&mut self, metrics: &DetailedMetrics) -> Vec<Alert> {
+        let now = metrics.timestamp;
+        let mut doc = LazyDocument(None);
+        let mut alerts = Vec::new();
+
+        for rule in self.rules.iter().chain(&self.config_rules) {
+            let state = self.states.entry(rule.name.clone()).or_default();
+            let (active, value) = rule.evaluate(metrics, &mut doc, state.is_firing());
+            state.value = value;
+            let Some(event) = state.update(active, now, rule.timing) else {
+                continue;
+            };
+
+            let threshold = match &rule.condition {
+                Condition::Threshold(threshold) => Some(threshold),
+                Condition::Custom(_) => None,
+            };
+            let alert = Alert {
+                rule: rule.name.clone(),
+                severity: rule.severity,
+                state: event,
+                message: rule.render(metrics, event, value),
+                metric: threshold.map(|t| t.metric.to_string()),
+                value,
+                threshold: threshold.map(|t| t.threshold),
+                active_since: state.active_since.unwrap_or(now),
+                timestamp: now,
+            };
+            for notifier in self.notifiers.iter().chain(&self.webhooks) {
+                notifier.notify(&alert);
+            }
+            alerts.push(alert);
+        }
+
+        // Events need no rule: each is notified once, as a firing alert
+        for event in &metrics.events {
+            let alert = event_alert(event, now);
+            for notifier in self.notifiers.iter().chain(&self.webhooks) {
+                notifier.notify(&alert);
+            }
+            alerts.push(alert);
+        }
+        alerts
+    }
+
+    // Check every published sample. When `config_path` is given, the rules
+    // and webhooks are reloaded whenever that file changes.
+    pub fn spawn(mut self, sampler: &SamplerHandle, config_path: Option<PathBuf>) -> io::Result<JoinHandle<()>> {
+        let modified = |path: &PathBuf| -> Option<SystemTime> {
+            std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        };
+        let mut last_modified = config_path.as_ref().and_then(modified);
+
+        sampler.spawn_consumer("alerts", move |metrics| {
+            if let Some(path) = &config_path {
+                let current = modified(path);
+                if current != last_modified {
+                    last_modified = current;
+                    // A deleted or half-written file keeps the current rules
+                    match Config::read_from(path).map_err(|e| e.to_string())
+                        .and_then(|config| self.load_config(&config).map_err(|e| e.to_string()))
+                    {
+                        Ok(()) => info!(
+                            "Reloaded {} alert rules and {} webhooks from {}",
+                            self.config_rules.len(), self.webhooks.len(), path.display()
+                        ),
+                        Err(e) => error!("Keeping previous alert rules, reload failed: {}", e),
+                    }
+                }
+            }
+            self.check_alerts(metrics);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::monitor::LeakSuspect;
+    use crate::procfs::pressure::Pressure;
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, alert: &Alert) {
+            self.0.lock().unwrap().push(format!("{} [{}] {}", alert.state, alert.severity, alert.message));
+        }
+    }
+
+    fn sample(secs: u64, used_memory: u64) -> DetailedMetrics {
+        let mut metrics = DetailedMetrics {
+            timestamp: std::time::UNIX_EPOCH + Duration::from_secs(secs),
+            ..DetailedMetrics::default()
+        };
+        metrics.basic.total_memory = 100;
+        metrics.basic.used_memory = used_memory;
+        metrics
+    }
+
+    #[test]
+    fn test_config_rules_fire_and_reload() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "mem", "metric": "basic.used_memory", "percent_of": "basic.total_memory",
+                "op": ">", "threshold": 90, "severity": "critical",
+                "message": "memory at {value}%"}"#,
+        ).unwrap();
+        let mut config = Config::default();
+        config.alerts.push(rule);
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = AlertManager::from_config(&config).unwrap();
+        manager.add_notifier(Box::new(Recorder(sent.clone())));
+
+        manager.check_alerts(&sample(0, 50));
+        manager.check_alerts(&sample(1, 95));
+        assert_eq!(*sent.lock().unwrap(), vec!["firing [critical] memory at 95.00%".to_string()]);
+
+        // A broken rule is rejected and the old rules stay loaded
+        config.alerts[0].metric = "basic..used_memory".to_string();
+        assert!(manager.load_rules(&config.alerts).is_err());
+        assert_eq!(manager.rule_count(), 1);
+        // So are clashing names and a clear threshold that can never resolve
+        config.alerts[0].metric = "basic.used_memory".to_string();
+        config.alerts.push(config.alerts[0].clone());
+        assert!(manager.load_rules(&config.alerts).is_err());
+        config.alerts.pop();
+        config.alerts[0].clear_threshold = Some(95.0);
+        assert!(manager.load_rules(&config.alerts).is_err());
+        assert_eq!(manager.rule_count(), 1);
+    }
+
+    #[test]
+    fn test_pressure_rule() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "stall", "metric": "pressure.memory.some.avg10", "op": ">", "threshold": 20}"#,
+        ).unwrap();
+        let mut manager = AlertManager::new();
+        manager.load_rules(&[rule]).unwrap();
+
+        // No PSI on this kernel: nothing to compare, nothing fires
+        assert!(manager.check_alerts(&sample(0, 50)).is_empty());
+
+        let mut stalled = sample(1, 50);
+        let mut memory = Pressure::default();
+        memory.some.avg10 = 35.0;
+        stalled.pressure.memory = Some(memory);
+        let alerts = manager.check_alerts(&stalled);
+        assert_eq!(alerts[0].state, AlertState::Firing);
+        assert_eq!(alerts[0].value, Some(35.0));
+    }
+
+    #[test]
+    fn test_leak_rule() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "leak", "metric": "leaks[0].growth_bytes_per_hour", "op": ">", "threshold": 1000}"#,
+        ).unwrap();
+        let mut manager = AlertManager::new();
+        manager.load_rules(&[rule]).unwrap();
+        assert!(manager.check_alerts(&sample(0, 50)).is_empty());
+
+        let mut leaking = sample(1, 50);
+        leaking.leaks.push(LeakSuspect {
+            pid: 7,
+            name: "app".to_string(),
+            start_time: 0,
+            memory: 4096,
+            growth_bytes_per_hour: 5000.0,
+            r_squared: 0.99,
+            monotonic_ratio: 1.0,
+            growth_bytes: 2048,
+            observed_secs: 1800,
+            samples: 60,
+        });
+        assert_eq!(manager.check_alerts(&leaking)[0].value, Some(5000.0));
+        // Resolves once no process is suspected any more
+        let resolved = manager.check_alerts(&sample(2, 50));
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+        assert_eq!(resolved[0].message, "leak: leaks[0].growth_bytes_per_hour is n/a (> 1000)");
+    }
+
+    #[test]
+    fn test_events_notify_once() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = AlertManager::new();
+        manager.add_notifier(Box::new(Recorder(sent.clone())));
+
+        let mut metrics = sample(0, 50);
+        metrics.events.push(MemoryEvent {
+            kind: MemoryEventKind::OomKill,
+            timestamp: metrics.timestamp,
+            cgroup: None,
+            pid: Some(42),
+            process: Some("java".to_string()),
+            memory: Some(1024),
+            count: 1,
+            message: "OOM killer killed process 42 (java)".to_string(),
+        });
+        let alerts = manager.check_alerts(&metrics);
+        assert_eq!((alerts[0].rule.as_str(), alerts[0].value), ("oom_kill", Some(1024.0)));
+        assert!(manager.check_alerts(&sample(1, 50)).is_empty());
+        assert_eq!(manager.firing().count(), 0);
+        assert_eq!(*sent.lock().unwrap(), vec!["firing [critical] OOM killer killed process 42 (java)".to_string()]);
+    }
+
+    #[test]
+    fn test_pending_hysteresis_and_resolution() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "mem", "metric": "basic.used_memory", "percent_of": "basic.total_memory",
+                "op": ">", "threshold": 90, "clear_threshold": 80, "for_secs": 5,
+                "message": "memory {state} at {value}%"}"#,
+        ).unwrap();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = AlertManager::new();
+        manager.load_rules(&[rule]).unwrap();
+        manager.add_notifier(Box::new(Recorder(sent.clone())));
+
+        // Sustained for 5s before it fires, then once only
+        for secs in 0..10 {
+            manager.check_alerts(&sample(secs, 95));
+            assert_eq!(manager.firing().count(), usize::from(secs >= 5));
+        }
+        assert_eq!(manager.state("mem").unwrap().active_since, Some(std::time::UNIX_EPOCH));
+        // Between the clear and fire thresholds it keeps firing
+        assert!(manager.check_alerts(&sample(10, 85)).is_empty());
+        let resolved = manager.check_alerts(&sample(11, 75));
+        assert_eq!(resolved[0].state, AlertState::Resolved);
+        assert_eq!(resolved[0].value, Some(75.0));
+        // ...and below the fire threshold it does not fire again
+        assert!(manager.check_alerts(&sample(12, 85)).is_empty());
+
+        assert_eq!(*sent.lock().unwrap(), vec![
+            "firing [warning] memory firing at 95.00%".to_string(),
+            "resolved [warning] memory resolved at 75.00%".to_string(),
+        ]);
+    }
+}
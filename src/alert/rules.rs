@@ -0,0 +1,315 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::MonitorError;
+use super::state::AlertState;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = ">=")]
+    GreaterOrEqual,
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = "<=")]
+    LessOrEqual,
+    #[serde(rename = "==")]
+    Equal,
+    #[serde(rename = "!=")]
+    NotEqual,
+}
+
+impl Comparison {
+    pub fn matches(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+    // `[key=value]` selects the first array element whose field matches
+    Filter(String, String),
+}
+
+// A path into the JSON form of `DetailedMetrics`, e.g. `basic.cpu_usage`,
+// `disks[name=/dev/sda].available_space` or `processes[0].memory`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl MetricPath {
+    pub fn parse(path: &str) -> Result<Self, MonitorError> {
+        let invalid = |reason: &str| MonitorError::Config(format!("Invalid metric path {:?}: {}", path, reason));
+        let mut segments = Vec::new();
+        let mut rest = path;
+
+        while !rest.is_empty() {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let field = &rest[..end];
+            if field.is_empty() {
+                return Err(invalid("empty field name"));
+            }
+            segments.push(Segment::Field(field.to_string()));
+            rest = &rest[end..];
+
+            while let Some(inner) = rest.strip_prefix('[') {
+                let close = inner.find(']').ok_or_else(|| invalid("unclosed '['"))?;
+                let selector = &inner[..close];
+                let segment = match selector.split_once('=') {
+                    Some((key, value)) if !key.is_empty() => Segment::Filter(key.to_string(), value.to_string()),
+                    Some(_) => return Err(invalid("empty filter key")),
+                    None => Segment::Index(selector.parse().map_err(|_| invalid("index is not a number"))?),
+                };
+                segments.push(segment);
+                rest = &inner[close + 1..];
+            }
+
+            rest = match rest.strip_prefix('.') {
+                Some("") => return Err(invalid("trailing '.'")),
+                Some(next) => next,
+                None if rest.is_empty() => rest,
+                None => return Err(invalid("expected '.' or '['")),
+            };
+        }
+
+        if segments.is_empty() {
+            return Err(invalid("path is empty"));
+        }
+        Ok(Self { raw: path.to_string(), segments })
+    }
+
+    // Numeric value at the path; booleans count as 0/1. None when the path
+    // does not exist in this sample (e.g. a disk that was unmounted).
+    pub fn resolve(&self, doc: &Value) -> Option<f64> {
+        let mut current = doc;
+        for segment in &self.segments {
+            current = match segment {
+                Segment::Field(name) => current.get(name)?,
+                Segment::Index(index) => current.get(*index)?,
+                Segment::Filter(key, expected) => current.as_array()?.iter().find(|item| {
+                    match item.get(key) {
+                        Some(Value::String(s)) => s == expected,
+                        Some(Value::Number(n)) => expected.parse::<f64>().ok() == n.as_f64(),
+                        Some(Value::Bool(b)) => expected.parse::<bool>().ok() == Some(*b),
+                        _ => false,
+                    }
+                })?,
+            };
+        }
+        match current {
+            Value::Number(n) => n.as_f64(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MetricPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+// A rule as written in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRuleConfig {
+    pub name: String,
+    pub metric: String,
+    // When set, the rule compares `100 * metric / percent_of`
+    #[serde(default)]
+    pub percent_of: Option<String>,
+    pub op: Comparison,
+    pub threshold: f64,
+    // Once firing, the rule only resolves when `op` no longer holds
+    // against this value, e.g. fire above 90 and clear below 85
+    #[serde(default)]
+    pub clear_threshold: Option<f64>,
+    // How long the condition must hold before the rule fires
+    #[serde(default)]
+    pub for_secs: u64,
+    // Re-notify while firing; by default a rule notifies once per firing
+    #[serde(default)]
+    pub repeat_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub severity: Severity,
+    // Supports {name}, {metric}, {value}, {op}, {threshold}, {severity} and {state}
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+pub const DEFAULT_MESSAGE: &str = "{name}: {metric} is {value} ({op} {threshold})";
+
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub metric: MetricPath,
+    pub percent_of: Option<MetricPath>,
+    pub op: Comparison,
+    pub threshold: f64,
+    pub clear_threshold: Option<f64>,
+}
+
+impl Threshold {
+    // A clear threshold must lie on the non-firing side of the threshold,
+    // or a firing rule could never resolve
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(clear) = self.clear_threshold else {
+            return Ok(());
+        };
+        let valid = match self.op {
+            Comparison::Greater | Comparison::GreaterOrEqual => clear <= self.threshold,
+            Comparison::Less | Comparison::LessOrEqual => clear >= self.threshold,
+            Comparison::Equal | Comparison::NotEqual => clear == self.threshold,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("clear_threshold {} is on the firing side of {} {}", clear, self.op, self.threshold))
+        }
+    }
+
+    pub fn value(&self, doc: &Value) -> Option<f64> {
+        let value = self.metric.resolve(doc)?;
+        match &self.percent_of {
+            Some(total) => match total.resolve(doc)? {
+                t if t != 0.0 => Some(value / t * 100.0),
+                _ => None,
+            },
+            None => Some(value),
+        }
+    }
+
+    // The observed value when the rule matches
+    pub fn evaluate(&self, doc: &Value) -> Option<f64> {
+        self.value(doc).filter(|v| self.op.matches(*v, self.threshold))
+    }
+
+    // Like `evaluate`, but a rule that is already firing is held against
+    // the clear threshold instead
+    pub fn evaluate_firing(&self, doc: &Value) -> Option<f64> {
+        let clear = self.clear_threshold.unwrap_or(self.threshold);
+        self.value(doc).filter(|v| self.op.matches(*v, clear))
+    }
+}
+
+pub fn render_template(
+    template: &str,
+    name: &str,
+    severity: Severity,
+    state: AlertState,
+    threshold: &Threshold,
+    // None when the metric is missing from the sample
+    value: Option<f64>,
+) -> String {
+    let value = value.map_or_else(|| "n/a".to_string(), |v| format!("{:.2}", v));
+    template
+        .replace("{name}", name)
+        .replace("{state}", &state.to_string())
+        .replace("{metric}", &threshold.metric.to_string())
+        .replace("{value}", &value)
+        .replace("{op}", &threshold.op.to_string())
+        .replace("{threshold}", &threshold.threshold.to_string())
+        .replace("{severity}", &severity.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_path_parsing_and_resolution() {
+        let doc = json!({
+            "basic": { "cpu_usage": 91.5, "used_memory": 3, "total_memory": 4 },
+            "disks": [
+                { "name": "/dev/sda1", "available_space": 10 },
+                { "name": "/dev/mapper/vg0-root", "available_space": 20 }
+            ],
+            "processes": [{ "pid": 42, "memory": 7 }]
+        });
+
+        let resolve = |p: &str| MetricPath::parse(p).unwrap().resolve(&doc);
+        assert_eq!(resolve("basic.cpu_usage"), Some(91.5));
+        assert_eq!(resolve("disks[name=/dev/mapper/vg0-root].available_space"), Some(20.0));
+        assert_eq!(resolve("processes[pid=42].memory"), Some(7.0));
+        assert_eq!(resolve("processes[0].memory"), Some(7.0));
+        assert_eq!(resolve("disks[name=/dev/sdb].available_space"), None);
+
+        for bad in ["", "basic.", "disks[name=x", "disks[x].y", "a..b", "disks[=x]"] {
+            assert!(MetricPath::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+
+        let rule = Threshold {
+            metric: MetricPath::parse("basic.used_memory").unwrap(),
+            percent_of: Some(MetricPath::parse("basic.total_memory").unwrap()),
+            op: Comparison::GreaterOrEqual,
+            threshold: 75.0,
+            clear_threshold: Some(70.0),
+        };
+        assert_eq!(rule.evaluate(&doc), Some(75.0));
+        assert_eq!(rule.evaluate_firing(&doc), Some(75.0));
+        assert_eq!(
+            render_template(DEFAULT_MESSAGE, "mem", Severity::Critical, AlertState::Firing, &rule, Some(75.0)),
+            "mem: basic.used_memory is 75.00 (>= 75)"
+        );
+        assert_eq!(
+            render_template(DEFAULT_MESSAGE, "mem", Severity::Critical, AlertState::Resolved, &rule, None),
+            "mem: basic.used_memory is n/a (>= 75)"
+        );
+    }
+
+    #[test]
+    fn test_rule_config_format() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "cpu", "metric": "basic.cpu_usage", "op": ">", "threshold": 90, "severity": "critical"}"#,
+        ).unwrap();
+        assert_eq!(rule.op, Comparison::Greater);
+        assert_eq!(rule.severity, Severity::Critical);
+        assert!(rule.message.is_none());
+        assert_eq!(rule.for_secs, 0);
+        assert!(rule.clear_threshold.is_none() && rule.repeat_interval_secs.is_none());
+    }
+}
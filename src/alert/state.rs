@@ -0,0 +1,162 @@
+use std::fmt;
+use std::time::{Duration, SystemTime};
+use serde::Serialize;
+use super::rules::Severity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Inactive,
+    // Condition holds but has not lasted for the rule's `for` duration yet
+    Pending,
+    Firing,
+    // Was firing and has cleared; behaves like Inactive otherwise
+    Resolved,
+}
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AlertState::Inactive => "inactive",
+            AlertState::Pending => "pending",
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        })
+    }
+}
+
+// What notifiers receive on every firing, repeat and resolution
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub severity: Severity,
+    // Firing or Resolved
+    pub state: AlertState,
+    pub message: String,
+    // Metric path, observed value and threshold for threshold rules
+    pub metric: Option<String>,
+    pub value: Option<f64>,
+    pub threshold: Option<f64>,
+    // When the condition started to hold
+    pub active_since: SystemTime,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub for_duration: Duration,
+    // None notifies once per firing
+    pub repeat_interval: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleState {
+    pub state: AlertState,
+    pub active_since: Option<SystemTime>,
+    pub last_notified: Option<SystemTime>,
+    pub value: Option<f64>,
+}
+
+impl Default for RuleState {
+    fn default() -> Self {
+        Self {
+            state: AlertState::Inactive,
+            active_since: None,
+            last_notified: None,
+            value: None,
+        }
+    }
+}
+
+impl RuleState {
+    pub fn is_firing(&self) -> bool {
+        self.state == AlertState::Firing
+    }
+
+    // Advance with the latest evaluation; returns the state to notify
+    // about (Firing or Resolved), if any
+    pub fn update(&mut self, active: bool, now: SystemTime, timing: Timing) -> Option<AlertState> {
+        let elapsed = |since: Option<SystemTime>| {
+            since.and_then(|s| now.duration_since(s).ok()).unwrap_or_default()
+        };
+
+        match (self.state, active) {
+            (AlertState::Inactive | AlertState::Resolved, true) => {
+                self.active_since = Some(now);
+                self.state = AlertState::Pending;
+                self.promote(now, timing, elapsed(self.active_since))
+            }
+            (AlertState::Pending, true) => self.promote(now, timing, elapsed(self.active_since)),
+            (AlertState::Pending, false) => {
+                self.state = AlertState::Inactive;
+                self.active_since = None;
+                None
+            }
+            (AlertState::Firing, true) => {
+                let repeat = timing.repeat_interval?;
+                if elapsed(self.last_notified) >= repeat {
+                    self.last_notified = Some(now);
+                    Some(AlertState::Firing)
+                } else {
+                    None
+                }
+            }
+            (AlertState::Firing, false) => {
+                self.state = AlertState::Resolved;
+                self.last_notified = Some(now);
+                Some(AlertState::Resolved)
+            }
+            (AlertState::Inactive | AlertState::Resolved, false) => None,
+        }
+    }
+
+    fn promote(&mut self, now: SystemTime, timing: Timing, pending_for: Duration) -> Option<AlertState> {
+        if pending_for < timing.for_duration {
+            return None;
+        }
+        self.state = AlertState::Firing;
+        self.last_notified = Some(now);
+        Some(AlertState::Firing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_pending_firing_repeat_resolved() {
+        let timing = Timing {
+            for_duration: Duration::from_secs(10),
+            repeat_interval: Some(Duration::from_secs(60)),
+        };
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let mut rule = RuleState::default();
+
+        assert_eq!(rule.update(true, at(0), timing), None);
+        assert_eq!(rule.state, AlertState::Pending);
+        // A blip shorter than `for` never fires
+        assert_eq!(rule.update(false, at(5), timing), None);
+        assert_eq!(rule.state, AlertState::Inactive);
+
+        assert_eq!(rule.update(true, at(100), timing), None);
+        assert_eq!(rule.update(true, at(110), timing), Some(AlertState::Firing));
+        assert_eq!(rule.active_since, Some(at(100)));
+        // Sustained condition: quiet until the repeat interval passes
+        assert_eq!(rule.update(true, at(111), timing), None);
+        assert_eq!(rule.update(true, at(170), timing), Some(AlertState::Firing));
+
+        assert_eq!(rule.update(false, at(171), timing), Some(AlertState::Resolved));
+        assert_eq!(rule.update(false, at(172), timing), None);
+        assert_eq!(rule.state, AlertState::Resolved);
+    }
+
+    #[test]
+    fn test_zero_for_fires_immediately_once() {
+        let timing = Timing { for_duration: Duration::ZERO, repeat_interval: None };
+        let mut rule = RuleState::default();
+        assert_eq!(rule.update(true, UNIX_EPOCH, timing), Some(AlertState::Firing));
+        assert_eq!(rule.update(true, UNIX_EPOCH + Duration::from_secs(3600), timing), None);
+    }
+}
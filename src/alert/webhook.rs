@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use crate::error::MonitorError;
+use crate::storage::timestamp_ms;
+use super::{Alert, Notifier};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    // Sent with every request, e.g. an Authorization header
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    // Further attempts after the first one fails
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    // Delay before the first retry; doubled for every retry after that, up
+    // to MAX_BACKOFF
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn default_timeout_secs() -> u64 {
+    5
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+// The JSON body POSTed for every alert
+#[derive(Debug, Serialize)]
+pub struct Payload<'a> {
+    pub rule: &'a str,
+    pub severity: String,
+    pub state: String,
+    pub message: &'a str,
+    pub metric: Option<&'a str>,
+    pub value: Option<f64>,
+    pub threshold: Option<f64>,
+    pub active_since: String,
+    pub timestamp: String,
+    pub timestamp_ms: i64,
+}
+
+impl<'a> Payload<'a> {
+    pub fn new(alert: &'a Alert) -> Self {
+        let rfc3339 = |time: SystemTime| DateTime::<Utc>::from(time).to_rfc3339();
+        Self {
+            rule: &alert.rule,
+            severity: alert.severity.to_string(),
+            state: alert.state.to_string(),
+            message: &alert.message,
+            metric: alert.metric.as_deref(),
+            value: alert.value,
+            threshold: alert.threshold,
+            active_since: rfc3339(alert.active_since),
+            timestamp: rfc3339(alert.timestamp),
+            timestamp_ms: timestamp_ms(alert.timestamp),
+        }
+    }
+}
+
+pub struct WebhookClient {
+    config: WebhookConfig,
+    agent: ureq::Agent,
+}
+
+impl WebhookClient {
+    pub fn new(config: WebhookConfig) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .build();
+        Self { config, agent }
+    }
+
+    // POST the alert, retrying with exponential backoff. Client errors
+    // (4xx) are not retried, the request would fail the same way again,
+    // except for timeouts (408) and rate limiting (429).
+    pub fn send(&self, alert: &Alert) -> Result<(), MonitorError> {
+        let body = serde_json::to_string(&Payload::new(alert))
+            .map_err(|e| MonitorError::Http(e.to_string()))?;
+        let mut backoff = Duration::from_millis(self.config.backoff_ms);
+        let mut attempt = 0;
+
+        loop {
+            let mut request = self.agent.post(&self.config.url).set("Content-Type", "application/json");
+            for (name, value) in &self.config.headers {
+                request = request.set(name, value);
+            }
+
+            let error = match request.send_string(&body) {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) && code != 408 && code != 429 => {
+                    return Err(MonitorError::Http(format!("{} returned {}", self.config.url, code)));
+                }
+                Err(e) => e,
+            };
+
+            if attempt >= self.config.retries {
+                return Err(MonitorError::Http(format!(
+                    "{} failed after {} attempts: {}", self.config.url, attempt + 1, error
+                )));
+            }
+            warn!("Webhook {} failed, retrying in {:?}: {}", self.config.url, backoff, error);
+            thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2).min(MAX_BACKOFF);
+            attempt += 1;
+        }
+    }
+}
+
+// Delivers alerts on a thread of its own, so a slow or unreachable
+// endpoint never holds up rule evaluation
+pub struct WebhookNotifier {
+    queue: Sender<Alert>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> std::io::Result<Self> {
+        let (queue, alerts) = mpsc::channel::<Alert>();
+        let client = WebhookClient::new(config);
+        thread::Builder::new().name("webhook".to_string()).spawn(move || {
+            for alert in alerts {
+                if let Err(e) = client.send(&alert) {
+                    error!("Dropping {} alert for {}: {}", alert.state, alert.rule, e);
+                }
+            }
+        })?;
+        Ok(Self { queue })
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, alert: &Alert) {
+        if self.queue.send(alert.clone()).is_err() {
+            error!("Webhook worker has stopped, dropping alert for {}", alert.rule);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::time::UNIX_EPOCH;
+    use crate::alert::{AlertState, Severity};
+
+    // Answers each connection with the next status and returns the
+    // request bodies and headers it saw
+    fn stub_server(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut headers = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    headers.push_str(&line);
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push((headers, String::from_utf8(body).unwrap()));
+                write!(reader.get_mut(), "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            rule: "mem".to_string(),
+            severity: Severity::Critical,
+            state: AlertState::Firing,
+            message: "memory at 95.00%".to_string(),
+            metric: Some("basic.used_memory".to_string()),
+            value: Some(95.0),
+            threshold: Some(90.0),
+            active_since: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_030),
+        }
+    }
+
+    fn config(url: String, retries: u32) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            headers: BTreeMap::from([("X-Token".to_string(), "secret".to_string())]),
+            timeout_secs: 5,
+            retries,
+            backoff_ms: 10,
+        }
+    }
+
+    #[test]
+    fn test_posts_payload_and_retries_server_errors() {
+        let (url, server) = stub_server(vec![503, 200]);
+        WebhookClient::new(config(url, 3)).send(&alert()).unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        assert!(headers.to_ascii_lowercase().contains("x-token: secret"));
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["rule"], "mem");
+        assert_eq!(payload["severity"], "critical");
+        assert_eq!(payload["state"], "firing");
+        assert_eq!(payload["value"], 95.0);
+        assert_eq!(payload["timestamp"], "2023-11-14T22:13:50+00:00");
+        assert_eq!(payload["timestamp_ms"], 1_700_000_030_000i64);
+    }
+
+    #[test]
+    fn test_gives_up_on_client_errors_and_after_retries() {
+        let (url, server) = stub_server(vec![404]);
+        assert!(WebhookClient::new(config(url, 3)).send(&alert()).is_err());
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        let (url, server) = stub_server(vec![429, 408, 200]);
+        WebhookClient::new(config(url, 3)).send(&alert()).unwrap();
+        assert_eq!(server.join().unwrap().len(), 3);
+
+        let (url, server) = stub_server(vec![500, 500]);
+        assert!(WebhookClient::new(config(url, 1)).send(&alert()).is_err());
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,326 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use chrono::{Local, TimeZone};
+use log::{error, info};
+use sysinfo::{System, SystemExt};
+use tokio::sync::broadcast::error::RecvError;
+use crate::alert::{Alert, AlertManager, LogNotifier};
+use crate::cli::{Args, Command};
+use crate::collector::CollectorRegistry;
+use crate::config::Config;
+use crate::display::Dashboard;
+use crate::error::MonitorError;
+use crate::logger;
+use crate::sampler::{Sampler, SamplerHandle};
+use crate::storage::{self, timestamp_ms, Aggregation, Metric, MetricsStorage, SeriesQuery};
+use crate::web;
+
+// Load the config named by `args`, apply the command line overrides and
+// run the requested command
+pub fn run(args: Args) -> Result<ExitCode, MonitorError> {
+    // Only the default config is created when missing; a path given on
+    // the command line is most likely a typo
+    let config_path = args.config_path();
+    let loaded = match &args.config {
+        Some(path) => Config::read_from(path),
+        None => Config::load_from(config_path),
+    };
+    let mut config = loaded.map_err(|e| MonitorError::Config(format!("{}: {}", config_path, e)))?;
+    if let Some(level) = &args.log_level {
+        config.log_level = level.clone();
+    }
+    if let Some(interval) = args.interval {
+        config.update_interval = interval;
+    }
+
+    let command = args.command();
+    logger::setup_logger_from_config(&config, command != Command::Tui)
+        .map_err(|e| MonitorError::Logger(e.to_string()))?;
+
+    match command {
+        Command::Serve => serve(&config, Path::new(config_path))?,
+        Command::Tui => tui(&config)?,
+        Command::Record { duration } => record(&config, duration.map(Duration::from_secs))?,
+        Command::Query { metric, since, bucket, agg, json } => {
+            query(&config, metric.as_deref(), since, bucket, &agg, json)?
+        }
+        Command::Export { format, since, output } => match export(&config, &format, since, output.as_deref()) {
+            // Piped into `head` and the like
+            Err(MonitorError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        },
+        Command::Check => return check(&config, args.interval.map(Duration::from_secs)),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn serve(config: &Config, config_path: &Path) -> Result<(), MonitorError> {
+    let sampler = Sampler::from_config(config).spawn()?;
+
+    // Persist every sample without blocking the web handlers
+    match MetricsStorage::from_config(&config.storage) {
+        Ok(storage) => {
+            storage.spawn_recorder(&sampler, config.storage.batch_size)?;
+            storage::spawn_compactor(config.storage.clone(), Duration::from_secs(config.update_interval))?;
+        }
+        Err(e) => error!("Metrics storage disabled: {}", e),
+    }
+    spawn_alerts(config, &sampler, config_path)?;
+
+    actix_web::rt::System::new().block_on(web::start_server(sampler, config))?;
+    Ok(())
+}
+
+fn spawn_alerts(config: &Config, sampler: &SamplerHandle, config_path: &Path) -> Result<(), MonitorError> {
+    match AlertManager::from_config(config) {
+        Ok(mut alerts) => {
+            alerts.add_notifier(Box::new(LogNotifier));
+            alerts.spawn(sampler, Some(config_path.to_path_buf()))?;
+        }
+        Err(e) => error!("Alerting disabled: {}", e),
+    }
+    Ok(())
+}
+
+fn tui(config: &Config) -> Result<(), MonitorError> {
+    let sampler = Sampler::from_config(config).spawn()?;
+    let mut dashboard = Dashboard::new()?;
+    dashboard.init()?;
+    let result = dashboard.run(&sampler, Duration::from_secs(config.display.refresh_rate.max(1)));
+    dashboard.cleanup()?;
+    sampler.stop();
+    Ok(result?)
+}
+
+// Like `serve` without the web server: sample, store and compact until
+// `duration` has passed (or forever)
+fn record(config: &Config, duration: Option<Duration>) -> Result<(), MonitorError> {
+    let mut storage = MetricsStorage::from_config(&config.storage)?;
+    let sampler = Sampler::from_config(config).spawn()?;
+    storage::spawn_compactor(config.storage.clone(), Duration::from_secs(config.update_interval))?;
+
+    let batch_size = config.storage.batch_size.max(1);
+    let mut updates = sampler.subscribe();
+    // Stopping the sampler closes `updates` once the last sample is read
+    if let Some(duration) = duration {
+        let sampler = sampler.clone();
+        thread::Builder::new().name("record-timer".to_string()).spawn(move || {
+            thread::sleep(duration);
+            sampler.stop();
+        })?;
+    }
+    let mut pending = Vec::with_capacity(batch_size);
+    let mut recorded = 0;
+    info!("Recording to {}", config.storage.path);
+
+    loop {
+        match updates.blocking_recv() {
+            Ok(snapshot) => {
+                pending.push((*snapshot).clone());
+                if pending.len() >= batch_size {
+                    storage.store_batch(&pending)?;
+                    recorded += pending.len();
+                    pending.clear();
+                }
+            }
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    sampler.stop();
+    storage.store_batch(&pending)?;
+    recorded += pending.len();
+    info!("Recorded {} samples", recorded);
+    Ok(())
+}
+
+fn query(
+    config: &Config,
+    metric: Option<&str>,
+    since_secs: u64,
+    bucket_secs: u64,
+    aggregation: &str,
+    json: bool,
+) -> Result<(), MonitorError> {
+    let storage = open_recorded(config)?;
+    let end_ms = timestamp_ms(SystemTime::now());
+    let start_ms = start_ms(end_ms, since_secs);
+
+    let metric = match metric {
+        Some(metric) => metric.parse::<Metric>().map_err(MonitorError::Config)?,
+        None => {
+            for metric in storage.list_metrics(start_ms, end_ms)? {
+                println!("{}", metric);
+            }
+            return Ok(());
+        }
+    };
+    let series = storage.query_series(&SeriesQuery {
+        metric,
+        start_ms,
+        end_ms,
+        bucket_secs,
+        aggregation: aggregation.parse::<Aggregation>().map_err(MonitorError::Config)?,
+    })?;
+
+    if json {
+        let json = serde_json::to_string_pretty(&series).map_err(|e| MonitorError::Config(e.to_string()))?;
+        println!("{}", json);
+        return Ok(());
+    }
+    for point in &series.points {
+        println!("{}  {:>16.2}  {:>6}", format_time(point.timestamp_ms), point.value, point.samples);
+    }
+    Ok(())
+}
+
+// Read-only access for the query commands, which must not create or
+// migrate the database
+fn open_recorded(config: &Config) -> Result<MetricsStorage, MonitorError> {
+    let path = Path::new(&config.storage.path);
+    if !path.exists() {
+        return Err(MonitorError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist, nothing has been recorded yet", path.display()),
+        )));
+    }
+    Ok(MetricsStorage::open_read_only(path)?)
+}
+
+// `since_secs` before `end_ms`, clamped rather than overflowing
+fn start_ms(end_ms: i64, since_secs: u64) -> i64 {
+    let since_ms = i64::try_from(since_secs).ok().and_then(|secs| secs.checked_mul(1000)).unwrap_or(i64::MAX);
+    end_ms.saturating_sub(since_ms)
+}
+
+// Every raw sample of every stored metric, one row per value
+fn export(config: &Config, format: &str, since_secs: u64, output: Option<&str>) -> Result<(), MonitorError> {
+    let storage = open_recorded(config)?;
+    let end_ms = timestamp_ms(SystemTime::now());
+    let start_ms = start_ms(end_ms, since_secs);
+
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    if format == "csv" {
+        writeln!(out, "timestamp_ms,metric,value")?;
+    }
+    for metric in storage.list_metrics(start_ms, end_ms)? {
+        let name = metric.to_string();
+        for (timestamp_ms, value) in storage.raw_points(&metric, start_ms, end_ms)? {
+            match format {
+                "csv" => writeln!(out, "{},{},{}", timestamp_ms, csv_field(&name), value)?,
+                _ => writeln!(
+                    out,
+                    "{}",
+                    serde_json::json!({ "timestamp_ms": timestamp_ms, "metric": name, "value": value })
+                )?,
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Evaluate the rules against a fresh sample. `for` durations are ignored,
+// there is only the one sample to judge by.
+fn check(config: &Config, interval: Option<Duration>) -> Result<ExitCode, MonitorError> {
+    let mut rules = config.alerts.clone();
+    for rule in &mut rules {
+        rule.for_secs = 0;
+    }
+    let mut manager = AlertManager::new();
+    manager.load_rules(&rules)?;
+
+    let mut registry = CollectorRegistry::from_config(config);
+    let settle = interval.unwrap_or(System::MINIMUM_CPU_UPDATE_INTERVAL);
+    let alerts = check_sample(&mut manager, &mut registry, &mut System::new(), settle);
+    for alert in &alerts {
+        println!("{} [{}] {}", alert.state, alert.severity, alert.message);
+    }
+
+    if alerts.is_empty() {
+        println!("OK: none of {} rules firing", manager.rule_count());
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::from(1))
+    }
+}
+
+// Rates and CPU usage are deltas against the previous run, so the first
+// sample only primes the collectors; the rules judge the second one
+fn check_sample(
+    manager: &mut AlertManager,
+    registry: &mut CollectorRegistry,
+    sys: &mut System,
+    settle: Duration,
+) -> Vec<Alert> {
+    registry.collect(sys);
+    thread::sleep(settle);
+    manager.check_alerts(&registry.collect(sys))
+}
+
+fn format_time(timestamp_ms: i64) -> String {
+    match Local.timestamp_millis_opt(timestamp_ms).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => timestamp_ms.to_string(),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertRuleConfig;
+    use crate::collector::Collector;
+    use crate::monitor::DetailedMetrics;
+
+    // Reports the change in its counter since the previous run, like the
+    // CPU and I/O collectors do
+    struct RateCollector {
+        counter: f64,
+        last: Option<f64>,
+    }
+
+    impl Collector for RateCollector {
+        fn name(&self) -> &str {
+            "rate"
+        }
+
+        fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+            self.counter += 10.0;
+            let rate = self.last.map_or(0.0, |last| self.counter - last);
+            self.last = Some(self.counter);
+            metrics.gauges.insert("rate".to_string(), rate);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_check_rate_rule_fires() {
+        let rule: AlertRuleConfig = serde_json::from_str(
+            r#"{"name": "busy", "metric": "gauges.rate", "op": ">", "threshold": 5}"#,
+        ).unwrap();
+        let mut manager = AlertManager::new();
+        manager.load_rules(&[rule]).unwrap();
+        let mut registry = CollectorRegistry::new();
+        registry.register(Box::new(RateCollector { counter: 0.0, last: None }));
+
+        let alerts = check_sample(&mut manager, &mut registry, &mut System::new(), Duration::ZERO);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].value, Some(10.0));
+    }
+}
@@ -1,18 +1,98 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 // System resource monitoring tool
 #[derive(Parser, Debug)]
 #[clap(author, version, about)]
 pub struct Args {
-    // Config file path
-    #[clap(short, long, default_value = "config.json")]
-    pub config: String,
+    // Created with the defaults when omitted and missing
+    #[clap(short, long, global = true, help = "Config file path [default: config.json]")]
+    pub config: Option<String>,
 
-    // Log level (debug, info, warn, error)
-    #[clap(short, long)]
+    #[clap(short, long, global = true, help = "Log level (debug, info, warn, error)")]
     pub log_level: Option<String>,
 
-    // Update interval in seconds
-    #[clap(short, long)]
+    #[clap(short, long, global = true, help = "Update interval in seconds")]
     pub interval: Option<u64>,
-} 
+
+    // `serve` when no subcommand is given
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    #[clap(about = "Serve the web dashboard and API (the default)")]
+    Serve,
+
+    #[clap(about = "Show the terminal dashboard")]
+    Tui,
+
+    #[clap(about = "Record samples to the metrics database without serving")]
+    Record {
+        // Stop after this many seconds instead of running until killed
+        #[clap(short, long, help = "Stop after this many seconds")]
+        duration: Option<u64>,
+    },
+
+    #[clap(about = "Print a stored series, or list the stored metrics")]
+    Query {
+        #[clap(help = "Metric such as cpu_usage or disk.available_space:/dev/sda1; lists metrics when omitted")]
+        metric: Option<String>,
+
+        #[clap(long, default_value = "3600", help = "How far back to look, in seconds")]
+        since: u64,
+
+        #[clap(long, default_value = "60", help = "Bucket size in seconds, 0 for raw samples")]
+        bucket: u64,
+
+        #[clap(long, default_value = "avg", help = "avg, min, max or p95")]
+        agg: String,
+
+        #[clap(long, help = "Print the series as JSON")]
+        json: bool,
+    },
+
+    #[clap(about = "Export raw stored samples as CSV or JSON lines")]
+    Export {
+        #[clap(long, default_value = "csv", possible_values = ["csv", "json"])]
+        format: String,
+
+        #[clap(long, default_value = "86400", help = "How far back to export, in seconds")]
+        since: u64,
+
+        #[clap(short, long, help = "Write to this file instead of stdout")]
+        output: Option<String>,
+    },
+
+    #[clap(about = "Evaluate the alert rules once; exits with 1 when any rule fires")]
+    Check,
+}
+
+pub const DEFAULT_CONFIG: &str = "config.json";
+
+impl Args {
+    pub fn config_path(&self) -> &str {
+        self.config.as_deref().unwrap_or(DEFAULT_CONFIG)
+    }
+
+    pub fn command(&self) -> Command {
+        self.command.clone().unwrap_or(Command::Serve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_flags_after_subcommand() {
+        let args = Args::parse_from(["memory_monitor", "query", "cpu_usage", "--since", "60", "-c", "other.json"]);
+        assert_eq!(args.config_path(), "other.json");
+        assert!(matches!(args.command(), Command::Query { metric: Some(m), since: 60, .. } if m == "cpu_usage"));
+
+        let args = Args::parse_from(["memory_monitor", "--interval", "5"]);
+        assert_eq!(args.interval, Some(5));
+        assert_eq!(args.command(), Command::Serve);
+        assert_eq!(args.config_path(), DEFAULT_CONFIG);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use sysinfo::System;
+use crate::config::CgroupConfig;
+use crate::error::MonitorError;
+use crate::monitor::{CgroupUsage, DetailedMetrics};
+use crate::procfs::cgroup::{self, CgroupStats};
+use super::Collector;
+
+// Walking the whole hierarchy reads several files per cgroup
+pub const CGROUP_TREE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Cumulative counters of one cgroup, for rates
+#[derive(Debug, Clone, Copy)]
+struct Counters {
+    usage_usec: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+// Memory, CPU and I/O of every cgroup on the host, with each process
+// tagged with the cgroup it belongs to. Between runs the process
+// collector carries the tags over.
+pub struct CgroupTreeCollector {
+    root: PathBuf,
+    last_sample: Option<(HashMap<String, Counters>, Instant)>,
+}
+
+impl CgroupTreeCollector {
+    pub fn new() -> Self {
+        Self::from_config(&CgroupConfig::default())
+    }
+
+    pub fn from_config(config: &CgroupConfig) -> Self {
+        Self { root: PathBuf::from(&config.root), last_sample: None }
+    }
+}
+
+impl Default for CgroupTreeCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for CgroupTreeCollector {
+    fn name(&self) -> &str {
+        "cgroups"
+    }
+
+    fn interval(&self) -> Duration {
+        CGROUP_TREE_INTERVAL
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // cgroup v1 and hybrid hosts have no controllers in the unified tree
+        if !self.root.join("cgroup.controllers").is_file() {
+            metrics.cgroups.clear();
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut counters = HashMap::new();
+        let mut usage = Vec::new();
+        for cg in cgroup::walk(&self.root) {
+            let stats = cgroup::read_stats(&cg);
+            let (read_bytes, write_bytes) = cgroup::read_io(&cg);
+            let current = Counters { usage_usec: stats.cpu_stat.usage_usec, read_bytes, write_bytes };
+            let previous = self.last_sample.as_ref().and_then(|(last, at)| {
+                let elapsed = now.saturating_duration_since(*at).as_secs_f64();
+                last.get(&cg.path).map(|counters| (*counters, elapsed))
+            });
+            usage.push(summarize(&cg.path, &stats, current, previous, cgroup::read_procs(&cg)));
+            counters.insert(cg.path, current);
+        }
+
+        let owners: HashMap<u32, &str> = usage.iter()
+            .flat_map(|cg| cg.pids.iter().map(move |&pid| (pid, cg.path.as_str())))
+            .collect();
+        for process in &mut metrics.processes {
+            process.cgroup = owners.get(&process.pid).map(|path| path.to_string());
+        }
+
+        usage.sort_by(|a, b| b.memory_current.cmp(&a.memory_current).then_with(|| a.path.cmp(&b.path)));
+        metrics.cgroups = usage;
+        self.last_sample = Some((counters, now));
+        Ok(())
+    }
+}
+
+// `previous` holds the counters from the last sample and the seconds since
+fn summarize(
+    path: &str,
+    stats: &CgroupStats,
+    current: Counters,
+    previous: Option<(Counters, f64)>,
+    pids: Vec<u32>,
+) -> CgroupUsage {
+    let (kind, name) = cgroup::classify(path);
+    let mut usage = CgroupUsage {
+        path: path.to_string(),
+        kind,
+        name,
+        memory_current: stats.memory_current,
+        memory_max: stats.memory_max,
+        cpu_usage: 0.0,
+        io_read_bytes: current.read_bytes,
+        io_write_bytes: current.write_bytes,
+        io_read_bytes_per_sec: 0.0,
+        io_write_bytes_per_sec: 0.0,
+        pids,
+        memory_high_events: stats.memory_events.high,
+        memory_max_events: stats.memory_events.max,
+        oom_kill_events: stats.memory_events.oom_kill,
+    };
+    if let Some((before, elapsed)) = previous.filter(|(_, elapsed)| *elapsed > 0.0) {
+        let rate = |now: u64, then: u64| now.saturating_sub(then) as f64 / elapsed;
+        usage.cpu_usage = rate(current.usage_usec, before.usage_usec) / 1e6 * 100.0;
+        usage.io_read_bytes_per_sec = rate(current.read_bytes, before.read_bytes);
+        usage.io_write_bytes_per_sec = rate(current.write_bytes, before.write_bytes);
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use sysinfo::SystemExt;
+    use crate::monitor::ProcessMetrics;
+    use crate::procfs::cgroup::CgroupKind;
+
+    #[test]
+    fn test_collect_from_fake_root() {
+        let root = tempfile::tempdir().unwrap();
+        let write = |dir: &str, file: &str, content: &str| {
+            let dir = root.path().join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(file), content).unwrap();
+        };
+        write("", "cgroup.controllers", "cpu memory io\n");
+        write("system.slice", "memory.current", "3000\n");
+        write("system.slice/nginx.service", "memory.current", "2000\n");
+        write("system.slice/nginx.service", "cgroup.procs", "10\n11\n");
+        write("system.slice/nginx.service", "cpu.stat", "usage_usec 1000000\n");
+
+        let mut collector = CgroupTreeCollector::from_config(&CgroupConfig {
+            root: root.path().to_string_lossy().into_owned(),
+            use_limits: None,
+        });
+        let mut metrics = DetailedMetrics {
+            processes: vec![
+                ProcessMetrics { pid: 10, ..ProcessMetrics::default() },
+                ProcessMetrics { pid: 99, ..ProcessMetrics::default() },
+            ],
+            ..DetailedMetrics::default()
+        };
+        collector.collect(&mut System::new(), &mut metrics).unwrap();
+
+        assert_eq!(metrics.cgroups.len(), 2);
+        assert_eq!(metrics.cgroups[0].path, "/system.slice");
+        let nginx = &metrics.cgroups[1];
+        assert_eq!((nginx.kind, nginx.name.as_str()), (CgroupKind::Service, "nginx.service"));
+        assert_eq!(nginx.pids, vec![10, 11]);
+        assert_eq!(metrics.processes[0].cgroup.as_deref(), Some("/system.slice/nginx.service"));
+        assert_eq!(metrics.processes[1].cgroup, None);
+
+        // Rates come from the next sample
+        let counters = |usage_usec| Counters { usage_usec, read_bytes: 0, write_bytes: 4096 };
+        let stats = CgroupStats::default();
+        let usage = summarize("/a.service", &stats, counters(1_500_000), Some((counters(1_000_000), 2.0)), Vec::new());
+        assert_eq!(usage.cpu_usage, 25.0);
+        assert_eq!(usage.io_write_bytes_per_sec, 0.0);
+    }
+}
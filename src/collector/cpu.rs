@@ -0,0 +1,50 @@
+use sysinfo::System;
+use crate::error::MonitorError;
+use crate::monitor::DetailedMetrics;
+use crate::procfs::stat::StatSample;
+use super::Collector;
+
+// User/system/iowait/steal/irq breakdown from /proc/stat. Per-core usage
+// and frequency come from sysinfo in the system collector.
+pub struct CpuCollector {
+    last_sample: Option<StatSample>,
+}
+
+impl CpuCollector {
+    pub fn new() -> Self {
+        Self { last_sample: None }
+    }
+}
+
+impl Default for CpuCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &str {
+        "cpu"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // Linux only; elsewhere the breakdown stays at zero
+        let current = match StatSample::read() {
+            Ok(sample) => sample,
+            Err(_) => return Ok(()),
+        };
+        // The first sample only sets the baseline
+        if let Some(previous) = &self.last_sample {
+            let cpu = &mut metrics.cpu;
+            cpu.breakdown = current.total.breakdown_since(&previous.total);
+            cpu.core_breakdowns = current.cores.iter()
+                .filter_map(|(name, times)| {
+                    let before = previous.cores.get(name)?;
+                    Some((name.clone(), times.breakdown_since(before)))
+                })
+                .collect();
+        }
+        self.last_sample = Some(current);
+        Ok(())
+    }
+}
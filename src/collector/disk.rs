@@ -0,0 +1,60 @@
+use sysinfo::{System, SystemExt, DiskExt};
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, DiskMetrics};
+use crate::procfs::diskstats::DiskStatsSample;
+use super::Collector;
+
+// Disk space from sysinfo plus I/O counters from /proc/diskstats
+pub struct DiskCollector {
+    last_sample: Option<DiskStatsSample>,
+}
+
+impl DiskCollector {
+    pub fn new() -> Self {
+        Self { last_sample: None }
+    }
+}
+
+impl Default for DiskCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &str {
+        "disks"
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        sys.refresh_disks_list();
+
+        // Not available on every platform; space is still reported without it
+        let current = DiskStatsSample::read().ok();
+
+        metrics.disks = sys.disks().iter().map(|disk| {
+            let name = disk.name().to_string_lossy().into_owned();
+            let io = current
+                .as_ref()
+                .map(|sample| sample.io(&name, self.last_sample.as_ref()))
+                .unwrap_or_default();
+
+            DiskMetrics {
+                name,
+                mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                file_system: String::from_utf8_lossy(disk.file_system()).into_owned(),
+                total_space: disk.total_space(),
+                available_space: disk.available_space(),
+                read_bytes: io.read_bytes,
+                write_bytes: io.write_bytes,
+                read_bytes_per_sec: io.read_bytes_per_sec,
+                write_bytes_per_sec: io.write_bytes_per_sec,
+            }
+        }).collect();
+
+        if current.is_some() {
+            self.last_sample = current;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use std::time::{Instant, SystemTime};
+use log::warn;
+use sysinfo::System;
+use crate::config::LeakConfig;
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, LeakSuspect, MemoryEvent, MemoryEventKind};
+use crate::process::LeakDetector;
+use crate::utils::format_bytes;
+use super::Collector;
+
+// Processes whose memory keeps growing, and an event the first time each
+// one is suspected: a leak that pauses and resumes is reported once per
+// process. Runs after the process collector.
+pub struct LeakCollector {
+    detector: LeakDetector,
+    // Suspects already reported, by pid and start time, until they exit
+    reported: HashSet<(u32, u64)>,
+}
+
+impl LeakCollector {
+    pub fn new() -> Self {
+        Self::from_config(&LeakConfig::default())
+    }
+
+    pub fn from_config(config: &LeakConfig) -> Self {
+        Self { detector: LeakDetector::new(config.clone()), reported: HashSet::new() }
+    }
+}
+
+impl Default for LeakCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for LeakCollector {
+    fn name(&self) -> &str {
+        "leaks"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        let suspects = self.detector.observe(&metrics.processes, Instant::now());
+        for suspect in &suspects {
+            if self.reported.insert((suspect.pid, suspect.start_time)) {
+                let event = leak_event(suspect);
+                warn!("{}", event.message);
+                metrics.events.push(event);
+            }
+        }
+        let live: HashSet<(u32, u64)> = metrics.processes.iter().map(|p| (p.pid, p.start_time)).collect();
+        self.reported.retain(|key| live.contains(key));
+        metrics.leaks = suspects;
+        Ok(())
+    }
+}
+
+fn leak_event(suspect: &LeakSuspect) -> MemoryEvent {
+    MemoryEvent {
+        kind: MemoryEventKind::LeakSuspected,
+        timestamp: SystemTime::now(),
+        cgroup: None,
+        pid: Some(suspect.pid),
+        process: Some(suspect.name.clone()),
+        memory: Some(suspect.memory),
+        count: 1,
+        message: format!(
+            "Suspected memory leak in process {} ({}): {} now, growing {}/h over {} min",
+            suspect.pid,
+            suspect.name,
+            format_bytes(suspect.memory),
+            format_bytes(suspect.growth_bytes_per_hour as u64),
+            suspect.observed_secs / 60,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysinfo::SystemExt;
+    use crate::monitor::ProcessMetrics;
+
+    // Leaks found and events raised by one sample of process 7
+    fn sample(collector: &mut LeakCollector, memory: Option<u64>) -> (usize, usize) {
+        let mut metrics = DetailedMetrics::default();
+        metrics.processes.extend(memory.map(|memory| ProcessMetrics { pid: 7, memory, ..ProcessMetrics::default() }));
+        collector.collect(&mut System::new(), &mut metrics).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        (metrics.leaks.len(), metrics.events.len())
+    }
+
+    #[test]
+    fn test_reports_each_process_once() {
+        let config = LeakConfig { min_samples: 3, sample_interval_secs: 0, min_growth_bytes_per_hour: 1, ..LeakConfig::default() };
+        let mut collector = LeakCollector::from_config(&config);
+
+        let grow: Vec<_> = (1..=4).map(|i| sample(&mut collector, Some(i << 20))).collect();
+        assert_eq!(grow.iter().map(|(_, events)| events).sum::<usize>(), 1);
+        assert_eq!(grow.last(), Some(&(1, 0)));
+        // Freed memory: no longer suspected, and not reported again when
+        // it grows past where it was
+        assert_eq!(sample(&mut collector, Some(1 << 20)), (0, 0));
+        assert!((5..=40).all(|i| sample(&mut collector, Some(i << 20)).1 == 0));
+        assert!(!collector.reported.is_empty());
+
+        // Once it exits the key is dropped
+        sample(&mut collector, None);
+        assert!(collector.reported.is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use sysinfo::System;
+use crate::error::MonitorError;
+use crate::monitor::DetailedMetrics;
+use crate::procfs::meminfo::MemInfo;
+use crate::procfs::vmstat::VmStatSample;
+use super::Collector;
+
+// Swap, page cache and kernel memory from /proc/meminfo, plus swap and
+// page fault rates from /proc/vmstat
+pub struct MemoryCollector {
+    last_sample: Option<VmStatSample>,
+}
+
+impl MemoryCollector {
+    pub fn new() -> Self {
+        Self { last_sample: None }
+    }
+}
+
+impl Default for MemoryCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for MemoryCollector {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // Linux only; elsewhere the breakdown stays at zero
+        let info = match MemInfo::read() {
+            Ok(info) => info,
+            Err(_) => return Ok(()),
+        };
+        let memory = &mut metrics.memory;
+        memory.swap_total = info.swap_total;
+        memory.swap_used = info.swap_used();
+        memory.swap_cached = info.swap_cached;
+        memory.buffers = info.buffers;
+        memory.cached = info.cached;
+        memory.shared = info.shared;
+        memory.slab = info.slab;
+        memory.slab_reclaimable = info.slab_reclaimable;
+        memory.dirty = info.dirty;
+        memory.writeback = info.writeback;
+        memory.committed_as = info.committed_as;
+        memory.commit_limit = info.commit_limit;
+        memory.hugepages_total = info.hugepages_total;
+        memory.hugepages_free = info.hugepages_free;
+        memory.hugepage_size = info.hugepage_size;
+
+        // Same for the counters: keep the meminfo breakdown without them
+        let current = match VmStatSample::read() {
+            Ok(current) => current,
+            Err(_) => return Ok(()),
+        };
+        memory.swap_in_pages = current.stat.pswpin;
+        memory.swap_out_pages = current.stat.pswpout;
+        memory.major_faults = current.stat.pgmajfault;
+        memory.oom_kills = current.stat.oom_kill;
+        if let Some(previous) = &self.last_sample {
+            let rates = current.rates(previous);
+            memory.swap_in_pages_per_sec = rates.pswpin;
+            memory.swap_out_pages_per_sec = rates.pswpout;
+            memory.page_faults_per_sec = rates.pgfault;
+            memory.major_faults_per_sec = rates.pgmajfault;
+        }
+        self.last_sample = Some(current);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+mod cgroup_tree;
+mod cpu;
+mod disk;
+mod leaks;
+mod memory;
+mod network;
+mod oom;
+mod pressure;
+mod self_cgroup;
+mod sockets;
+mod process;
+mod process_memory;
+mod system;
+mod temperature;
+
+pub use self::cgroup_tree::CgroupTreeCollector;
+pub use self::cpu::CpuCollector;
+pub use self::disk::DiskCollector;
+pub use self::leaks::LeakCollector;
+pub use self::memory::MemoryCollector;
+pub use self::network::NetworkCollector;
+pub use self::oom::OomCollector;
+pub use self::pressure::PressureCollector;
+pub use self::self_cgroup::SelfCgroupCollector;
+pub use self::sockets::SocketCollector;
+pub use self::process::ProcessCollector;
+pub use self::process_memory::ProcessMemoryCollector;
+pub use self::system::SystemCollector;
+pub use self::temperature::TemperatureCollector;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime};
+use log::warn;
+use sysinfo::System;
+use crate::config::{CollectorConfig, Config};
+use crate::error::MonitorError;
+use crate::monitor::DetailedMetrics;
+
+// A source of metrics. Each collector fills in its own part of
+// `DetailedMetrics`; fields it does not touch keep the value from the
+// previous sample, so collectors with longer intervals stay visible.
+pub trait Collector: Send {
+    // Unique name, used as the key in `Config::collectors`
+    fn name(&self) -> &str;
+
+    // How often the collector should run; zero means on every sample
+    fn interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError>;
+}
+
+struct Entry {
+    collector: Box<dyn Collector>,
+    enabled: bool,
+    interval: Duration,
+    last_run: Option<Instant>,
+}
+
+impl Entry {
+    fn is_due(&self, now: Instant) -> bool {
+        match self.last_run {
+            Some(last) => now.saturating_duration_since(last) >= self.interval,
+            None => true,
+        }
+    }
+}
+
+pub struct CollectorRegistry {
+    entries: Vec<Entry>,
+    overrides: BTreeMap<String, CollectorConfig>,
+    last: DetailedMetrics,
+}
+
+impl Default for CollectorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CollectorRegistry {
+    // An empty registry; see `with_defaults` for the built-in collectors
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            overrides: BTreeMap::new(),
+            last: DetailedMetrics::default(),
+        }
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(SystemCollector::new()));
+        registry.register(Box::new(CpuCollector::new()));
+        registry.register(Box::new(MemoryCollector::new()));
+        registry.register(Box::new(PressureCollector::new()));
+        // After the system collector, whose figures it may rescale
+        registry.register(Box::new(SelfCgroupCollector::new()));
+        registry.register(Box::new(NetworkCollector::new()));
+        registry.register(Box::new(ProcessCollector::new()));
+        // After the process collector, whose process list they use
+        registry.register(Box::new(ProcessMemoryCollector::new()));
+        registry.register(Box::new(LeakCollector::new()));
+        registry.register(Box::new(SocketCollector::new()));
+        registry.register(Box::new(CgroupTreeCollector::new()));
+        // After the memory, process and cgroup tree collectors
+        registry.register(Box::new(OomCollector::new()));
+        registry.register(Box::new(TemperatureCollector::new()));
+        registry.register(Box::new(DiskCollector::new()));
+        registry
+    }
+
+    // Built-in collectors with the per-collector settings from the config
+    // applied. Collectors registered later pick up their settings too.
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::new();
+        registry.overrides = config.collectors.clone();
+        for entry in Self::with_defaults().entries {
+            registry.register(entry.collector);
+        }
+        registry.register(Box::new(NetworkCollector::from_config(&config.network)));
+        registry.register(Box::new(SelfCgroupCollector::from_config(&config.cgroup)));
+        registry.register(Box::new(CgroupTreeCollector::from_config(&config.cgroup)));
+        registry.register(Box::new(ProcessMemoryCollector::from_config(&config.process)));
+        registry.register(Box::new(LeakCollector::from_config(&config.leaks)));
+        registry
+    }
+
+    // Register a collector. A collector with the same name replaces the
+    // existing one, so built-ins can be swapped for in-house versions.
+    pub fn register(&mut self, collector: Box<dyn Collector>) {
+        let mut entry = Entry {
+            interval: collector.interval(),
+            collector,
+            enabled: true,
+            last_run: None,
+        };
+        if let Some(cfg) = self.overrides.get(entry.collector.name()) {
+            entry.enabled = cfg.enabled;
+            if let Some(secs) = cfg.interval {
+                entry.interval = Duration::from_secs(secs);
+            }
+        }
+
+        match self.entries.iter_mut().find(|e| e.collector.name() == entry.collector.name()) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    // Returns false if no collector with that name is registered
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        self.entry_mut(name).map(|e| e.enabled = enabled).is_some()
+    }
+
+    pub fn set_interval(&mut self, name: &str, interval: Duration) -> bool {
+        self.entry_mut(name).map(|e| e.interval = interval).is_some()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.collector.name()).collect()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.entries.iter().any(|e| e.enabled && e.collector.name() == name)
+    }
+
+    // Run every enabled collector that is due and assemble the result
+    pub fn collect(&mut self, sys: &mut System) -> DetailedMetrics {
+        let now = Instant::now();
+        let mut metrics = self.last.clone();
+        // Events belong to the sample they were detected in
+        metrics.events.clear();
+
+        for entry in self.entries.iter_mut().filter(|e| e.enabled) {
+            if !entry.is_due(now) {
+                continue;
+            }
+            if let Err(e) = entry.collector.collect(sys, &mut metrics) {
+                warn!("Collector {} failed: {}", entry.collector.name(), e);
+            }
+            entry.last_run = Some(now);
+        }
+
+        metrics.timestamp = SystemTime::now();
+        self.last = metrics.clone();
+        metrics
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|e| e.collector.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysinfo::SystemExt;
+
+    struct CountingCollector {
+        calls: f64,
+        interval: Duration,
+    }
+
+    impl Collector for CountingCollector {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn interval(&self) -> Duration {
+            self.interval
+        }
+
+        fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+            self.calls += 1.0;
+            metrics.gauges.insert("calls".to_string(), self.calls);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_collector_and_interval() {
+        let mut sys = System::new();
+        let mut registry = CollectorRegistry::new();
+        registry.register(Box::new(CountingCollector { calls: 0.0, interval: Duration::from_secs(3600) }));
+
+        assert_eq!(registry.collect(&mut sys).gauges["calls"], 1.0);
+        // Not due yet: the previous value is carried over
+        assert_eq!(registry.collect(&mut sys).gauges["calls"], 1.0);
+
+        registry.set_interval("counter", Duration::ZERO);
+        assert_eq!(registry.collect(&mut sys).gauges["calls"], 2.0);
+
+        assert!(registry.set_enabled("counter", false));
+        assert_eq!(registry.collect(&mut sys).gauges["calls"], 2.0);
+        assert!(!registry.set_enabled("missing", false));
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let mut config = Config::default();
+        config.collectors.insert(
+            "temperatures".to_string(),
+            CollectorConfig { enabled: false, interval: None },
+        );
+        let registry = CollectorRegistry::from_config(&config);
+        assert!(registry.names().contains(&"temperatures"));
+        assert!(!registry.is_enabled("temperatures"));
+        assert!(registry.is_enabled("system"));
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use sysinfo::{System, SystemExt, NetworkExt};
+use crate::config::NetworkConfig;
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, InterfaceMetrics};
+use crate::procfs::netdev::{InterfaceStat, NetDevSample};
+use super::Collector;
+
+// Per-interface counters from /proc/net/dev (sysinfo elsewhere, which has
+// no drop counts) with rates between samples
+pub struct NetworkCollector {
+    config: NetworkConfig,
+    last_sample: Option<NetDevSample>,
+}
+
+impl NetworkCollector {
+    pub fn new() -> Self {
+        Self::from_config(&NetworkConfig::default())
+    }
+
+    pub fn from_config(config: &NetworkConfig) -> Self {
+        Self { config: config.clone(), last_sample: None }
+    }
+
+    fn is_included(&self, name: &str) -> bool {
+        let matches = |patterns: &[String]| patterns.iter().any(|p| glob_match(p, name));
+        (self.config.include.is_empty() || matches(&self.config.include)) && !matches(&self.config.exclude)
+    }
+}
+
+impl Default for NetworkCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for NetworkCollector {
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        let current = match NetDevSample::read() {
+            Ok(sample) => sample,
+            Err(_) => {
+                sys.refresh_networks_list();
+                NetDevSample::from_stats(sysinfo_stats(sys))
+            }
+        };
+
+        let interfaces: Vec<_> = current.stats.values()
+            .filter(|stat| self.is_included(&stat.name))
+            .map(|stat| {
+                let rates = self.last_sample.as_ref().map(|prev| current.rates(&stat.name, prev)).unwrap_or_default();
+                InterfaceMetrics {
+                    name: stat.name.clone(),
+                    rx_bytes: stat.rx_bytes,
+                    tx_bytes: stat.tx_bytes,
+                    rx_packets: stat.rx_packets,
+                    tx_packets: stat.tx_packets,
+                    rx_errors: stat.rx_errors,
+                    tx_errors: stat.tx_errors,
+                    rx_dropped: stat.rx_dropped,
+                    tx_dropped: stat.tx_dropped,
+                    rx_bytes_per_sec: rates.rx_bytes,
+                    tx_bytes_per_sec: rates.tx_bytes,
+                    rx_packets_per_sec: rates.rx_packets,
+                    tx_packets_per_sec: rates.tx_packets,
+                }
+            })
+            .collect();
+
+        let network = &mut metrics.network;
+        network.rx_bytes = interfaces.iter().map(|i| i.rx_bytes).sum();
+        network.tx_bytes = interfaces.iter().map(|i| i.tx_bytes).sum();
+        network.rx_bytes_per_sec = interfaces.iter().map(|i| i.rx_bytes_per_sec).sum();
+        network.tx_bytes_per_sec = interfaces.iter().map(|i| i.tx_bytes_per_sec).sum();
+        network.interfaces = interfaces;
+
+        self.last_sample = Some(current);
+        Ok(())
+    }
+}
+
+fn sysinfo_stats(sys: &System) -> BTreeMap<String, InterfaceStat> {
+    sys.networks()
+        .into_iter()
+        .map(|(name, data)| {
+            let stat = InterfaceStat {
+                name: name.clone(),
+                rx_bytes: data.total_received(),
+                rx_packets: data.total_packets_received(),
+                rx_errors: data.total_errors_on_received(),
+                tx_bytes: data.total_transmitted(),
+                tx_packets: data.total_packets_transmitted(),
+                tx_errors: data.total_errors_on_transmitted(),
+                ..InterfaceStat::default()
+            };
+            (name.clone(), stat)
+        })
+        .collect()
+}
+
+// Shell-style matching where `*` stands for any run of characters
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let tail = match name.strip_prefix(prefix) {
+                Some(tail) => tail,
+                None => return false,
+            };
+            (0..=tail.len()).filter(|&i| tail.is_char_boundary(i)).any(|i| glob_match(rest, &tail[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_filter() {
+        assert!(glob_match("veth*", "veth1a2b"));
+        assert!(glob_match("*br*0", "virbr0"));
+        assert!(!glob_match("veth*", "eth0"));
+        assert!(!glob_match("lo", "lo0"));
+
+        let collector = NetworkCollector::new();
+        assert!(collector.is_included("eth0"));
+        assert!(!collector.is_included("lo"));
+        assert!(!collector.is_included("veth9"));
+
+        let only_wifi = NetworkCollector::from_config(&NetworkConfig {
+            include: vec!["wl*".to_string()],
+            exclude: Vec::new(),
+        });
+        assert!(only_wifi.is_included("wlan0"));
+        assert!(!only_wifi.is_included("eth0"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime};
+use log::warn;
+use sysinfo::System;
+use crate::error::MonitorError;
+use crate::monitor::{CgroupUsage, DetailedMetrics, MemoryEvent, MemoryEventKind, ProcessMetrics};
+use crate::utils::format_bytes;
+use super::cgroup_tree::CGROUP_TREE_INTERVAL;
+use super::Collector;
+
+// memory.high throttling can go on for minutes; report it at most this often
+// per cgroup, with the count since the last report
+const PRESSURE_EVENT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Counters at the last reported pressure event of a cgroup
+struct Reported {
+    high: u64,
+    max: u64,
+    at: Instant,
+}
+
+// OOM kills from the vmstat and memory.events counters, attributed to the
+// processes that disappeared since the previous sample, and memory.high /
+// memory.max events of workload cgroups. Runs after the process, memory and
+// cgroup tree collectors, and as often as the latter: cgroup counters and
+// the processes that vanished must cover the same stretch of time.
+pub struct OomCollector {
+    last_oom_kills: Option<u64>,
+    last_cgroup_kills: HashMap<String, u64>,
+    // Keyed by pid and start time, so a reused pid is a different process
+    last_processes: HashMap<(u32, u64), ProcessMetrics>,
+    reported: HashMap<String, Reported>,
+}
+
+impl OomCollector {
+    pub fn new() -> Self {
+        Self {
+            last_oom_kills: None,
+            last_cgroup_kills: HashMap::new(),
+            last_processes: HashMap::new(),
+            reported: HashMap::new(),
+        }
+    }
+
+    fn pressure_events(&mut self, cgroups: &[CgroupUsage], now: Instant) -> Vec<MemoryEvent> {
+        let mut events = Vec::new();
+        for cg in cgroups.iter().filter(|cg| cg.kind.is_workload()) {
+            // Counts from before we first saw the cgroup are not news
+            let reported = self.reported.entry(cg.path.clone()).or_insert(Reported {
+                high: cg.memory_high_events,
+                max: cg.memory_max_events,
+                at: now,
+            });
+            if now.saturating_duration_since(reported.at) < PRESSURE_EVENT_INTERVAL {
+                continue;
+            }
+            let max = cg.memory_max_events.saturating_sub(reported.max);
+            let high = cg.memory_high_events.saturating_sub(reported.high);
+            // Hitting the hard limit is the more serious of the two
+            let (kind, count, limit) = if max > 0 {
+                (MemoryEventKind::MemoryMax, max, "memory.max")
+            } else if high > 0 {
+                (MemoryEventKind::MemoryHigh, high, "memory.high")
+            } else {
+                continue;
+            };
+            events.push(MemoryEvent {
+                kind,
+                timestamp: SystemTime::now(),
+                cgroup: Some(cg.path.clone()),
+                pid: None,
+                process: None,
+                memory: Some(cg.memory_current),
+                count,
+                message: format!("{} reached {} {} times ({} in use)", cg.name, limit, count, format_bytes(cg.memory_current)),
+            });
+            *reported = Reported { high: cg.memory_high_events, max: cg.memory_max_events, at: now };
+        }
+        let paths: Vec<&str> = cgroups.iter().map(|cg| cg.path.as_str()).collect();
+        self.reported.retain(|path, _| paths.contains(&path.as_str()));
+        events
+    }
+}
+
+impl Default for OomCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for OomCollector {
+    fn name(&self) -> &str {
+        "oom"
+    }
+
+    fn interval(&self) -> Duration {
+        CGROUP_TREE_INTERVAL
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        let host_kills = self.last_oom_kills.map_or(0, |last| metrics.memory.oom_kills.saturating_sub(last));
+        let cgroup_kills: Vec<(&str, u64)> = metrics.cgroups.iter()
+            .filter_map(|cg| {
+                let last = self.last_cgroup_kills.get(&cg.path)?;
+                Some((cg.path.as_str(), cg.oom_kill_events.saturating_sub(*last)))
+            })
+            .filter(|(_, kills)| *kills > 0)
+            .collect();
+        let vanished: Vec<&ProcessMetrics> = {
+            let current: HashSet<(u32, u64)> = metrics.processes.iter().map(|p| (p.pid, p.start_time)).collect();
+            self.last_processes.iter().filter(|(key, _)| !current.contains(key)).map(|(_, p)| p).collect()
+        };
+
+        let mut events = attribute_kills(host_kills, &cgroup_kills, vanished);
+        events.extend(self.pressure_events(&metrics.cgroups, Instant::now()));
+        for event in &events {
+            warn!("{}", event.message);
+        }
+        metrics.events.extend(events);
+
+        self.last_oom_kills = Some(metrics.memory.oom_kills);
+        self.last_cgroup_kills = metrics.cgroups.iter().map(|cg| (cg.path.clone(), cg.oom_kill_events)).collect();
+        self.last_processes = metrics.processes.iter().map(|p| ((p.pid, p.start_time), p.clone())).collect();
+        Ok(())
+    }
+}
+
+// One event per kill. memory.events counts a kill in the cgroup and all its
+// ancestors, so each kill is attributed to the deepest cgroup that counted
+// it; the victim is the largest process that vanished from that cgroup,
+// which is what the OOM killer picks. Kills left over from the host-wide
+// counter (outside any cgroup limit) take the largest remaining processes.
+fn attribute_kills(host_kills: u64, cgroup_kills: &[(&str, u64)], mut vanished: Vec<&ProcessMetrics>) -> Vec<MemoryEvent> {
+    vanished.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid)));
+    let within = |process: &ProcessMetrics, path: &str| {
+        process.cgroup.as_deref().is_some_and(|cg| cg == path || cg.starts_with(&format!("{}/", path)))
+    };
+
+    let mut events = Vec::new();
+    let mut attributed = 0;
+    let is_below = |path: &str, ancestor: &str| path.starts_with(&format!("{}/", ancestor));
+    // Deepest first, so the victims an ancestor can choose from exclude
+    // those its descendants' kills already account for
+    let mut by_depth = cgroup_kills.to_vec();
+    by_depth.sort_by_key(|(path, _)| std::cmp::Reverse(path.matches('/').count()));
+    for &(path, kills) in &by_depth {
+        // An ancestor's count includes its descendants'. Only the nearest
+        // counting descendants are subtracted, theirs include the rest.
+        let nested: u64 = cgroup_kills.iter()
+            .filter(|(other, _)| is_below(other, path))
+            .filter(|(other, _)| !cgroup_kills.iter().any(|(mid, _)| is_below(other, mid) && is_below(mid, path)))
+            .map(|(_, kills)| kills)
+            .sum();
+        let kills = kills.saturating_sub(nested);
+        for _ in 0..kills {
+            let victim = vanished.iter().position(|p| within(p, path)).map(|i| vanished.remove(i));
+            events.push(oom_kill_event(Some(path), victim));
+        }
+        attributed += kills;
+    }
+    for _ in 0..host_kills.saturating_sub(attributed) {
+        let victim = if vanished.is_empty() { None } else { Some(vanished.remove(0)) };
+        events.push(oom_kill_event(None, victim));
+    }
+    events
+}
+
+fn oom_kill_event(cgroup: Option<&str>, victim: Option<&ProcessMetrics>) -> MemoryEvent {
+    let mut message = match victim {
+        Some(p) => format!("OOM killer killed process {} ({}) using {}", p.pid, p.name, format_bytes(p.memory)),
+        None => "OOM killer killed a process".to_string(),
+    };
+    if let Some(path) = cgroup {
+        message.push_str(&format!(" in cgroup {}", path));
+    }
+    MemoryEvent {
+        kind: MemoryEventKind::OomKill,
+        timestamp: SystemTime::now(),
+        cgroup: cgroup.map(str::to_string),
+        pid: victim.map(|p| p.pid),
+        process: victim.map(|p| p.name.clone()),
+        memory: victim.map(|p| p.memory),
+        count: 1,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, memory: u64, cgroup: &str) -> ProcessMetrics {
+        ProcessMetrics {
+            pid,
+            name: format!("p{}", pid),
+            memory,
+            cgroup: Some(cgroup.to_string()),
+            ..ProcessMetrics::default()
+        }
+    }
+
+    #[test]
+    fn test_attribute_kills() {
+        let big = process(1, 900, "/system.slice/db.service");
+        let small = process(2, 100, "/kubepods/pod1/abc");
+        let other = process(3, 500, "/kubepods/pod1/def");
+        let exited = process(4, 50, "/user.slice");
+
+        // One kill in a container: counted by the container and its pod,
+        // and in the host counter. Exits elsewhere are not blamed.
+        let events = attribute_kills(1, &[("/kubepods/pod1", 1), ("/kubepods/pod1/abc", 1)], vec![&big, &small, &exited]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].pid, Some(2));
+        assert_eq!(events[0].cgroup.as_deref(), Some("/kubepods/pod1/abc"));
+        assert_eq!(events[0].message, "OOM killer killed process 2 (p2) using 100.00 B in cgroup /kubepods/pod1/abc");
+
+        // Host-wide kill: the largest vanished process
+        let events = attribute_kills(1, &[], vec![&small, &exited, &big]);
+        assert_eq!((events[0].pid, events[0].cgroup.as_deref()), (Some(1), None));
+
+        // Pod-level limit: either container's process may go, the larger first
+        let events = attribute_kills(2, &[("/kubepods/pod1", 2)], vec![&small, &other]);
+        assert_eq!(events.iter().map(|e| e.pid).collect::<Vec<_>>(), vec![Some(3), Some(2)]);
+
+        // Three levels counting: 3 at the root include the pod's 2, which
+        // include the container's 1
+        let pod = process(5, 300, "/kubepods/pod1");
+        let events = attribute_kills(3, &[("/kubepods", 3), ("/kubepods/pod1", 2), ("/kubepods/pod1/abc", 1)], vec![&small, &pod, &other]);
+        let kills: Vec<_> = events.iter().map(|e| (e.cgroup.as_deref().unwrap(), e.pid.unwrap())).collect();
+        assert_eq!(kills, vec![("/kubepods/pod1/abc", 2), ("/kubepods/pod1", 3), ("/kubepods", 5)]);
+
+        // Killed between samples and never seen
+        let events = attribute_kills(1, &[], Vec::new());
+        assert_eq!(events[0].pid, None);
+        assert!(attribute_kills(0, &[], vec![&exited]).is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use sysinfo::System;
+use crate::error::MonitorError;
+use crate::monitor::DetailedMetrics;
+use crate::procfs::pressure::{self, PRESSURE_DIR};
+use super::Collector;
+
+// CPU, memory and I/O stall averages from /proc/pressure (Linux 4.20+)
+pub struct PressureCollector {
+    dir: PathBuf,
+}
+
+impl PressureCollector {
+    pub fn new() -> Self {
+        Self::with_dir(PRESSURE_DIR)
+    }
+
+    // Read the PSI files from somewhere else, e.g. a cgroup v2 directory
+    // (with cpu.pressure, memory.pressure and io.pressure)
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl Default for PressureCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for PressureCollector {
+    fn name(&self) -> &str {
+        "pressure"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        let psi = &mut metrics.pressure;
+        psi.cpu = pressure::read(&self.dir, "cpu").ok();
+        psi.memory = pressure::read(&self.dir, "memory").ok();
+        psi.io = pressure::read(&self.dir, "io").ok();
+        Ok(())
+    }
+}
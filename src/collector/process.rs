@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use sysinfo::{System, SystemExt, ProcessExt, PidExt, UserExt};
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, ProcessMetrics};
+use super::Collector;
+
+pub struct ProcessCollector {
+    users_loaded: bool,
+}
+
+impl ProcessCollector {
+    pub fn new() -> Self {
+        Self { users_loaded: false }
+    }
+}
+
+impl Default for ProcessCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for ProcessCollector {
+    fn name(&self) -> &str {
+        "processes"
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // Users rarely change; load them once for uid to name lookups
+        if !self.users_loaded {
+            sys.refresh_users_list();
+            self.users_loaded = true;
+        }
+        sys.refresh_processes();
+
+        // The cgroup tree collector runs less often; keep its tags
+        let cgroups: HashMap<(u32, u64), String> = metrics.processes.iter_mut()
+            .filter_map(|p| Some(((p.pid, p.start_time), p.cgroup.take()?)))
+            .collect();
+        metrics.processes = sys.processes()
+            .values()
+            .map(|process| ProcessMetrics {
+                pid: process.pid().as_u32(),
+                name: process.name().to_string(),
+                cpu_usage: process.cpu_usage(),
+                memory: process.memory(),
+                status: process.status().to_string(),
+                parent_pid: process.parent().map(|pid| pid.as_u32()),
+                user: process.user_id().map(|uid| match sys.get_user_by_id(uid) {
+                    Some(user) => user.name().to_string(),
+                    None => uid.to_string(),
+                }),
+                command: process.cmd().join(" "),
+                start_time: process.start_time(),
+                // Filled in by the cgroup tree and process memory collectors
+                cgroup: cgroups.get(&(process.pid().as_u32(), process.start_time())).cloned(),
+                memory_detail: None,
+            })
+            .collect();
+        Ok(())
+    }
+}
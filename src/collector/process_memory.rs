@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+use sysinfo::System;
+use crate::config::ProcessConfig;
+use crate::error::MonitorError;
+use crate::monitor::DetailedMetrics;
+use crate::process::{read_memory, PROC_ROOT};
+use super::Collector;
+
+// RSS/PSS/USS, swap and the anonymous/file split for the largest
+// processes. smaps_rollup walks the page tables, so it is not read for
+// every process. Runs after the process collector.
+pub struct ProcessMemoryCollector {
+    proc_root: PathBuf,
+    count: usize,
+}
+
+impl ProcessMemoryCollector {
+    pub fn new() -> Self {
+        Self::from_config(&ProcessConfig::default())
+    }
+
+    pub fn from_config(config: &ProcessConfig) -> Self {
+        Self { proc_root: PathBuf::from(PROC_ROOT), count: config.detail_count }
+    }
+}
+
+impl Default for ProcessMemoryCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for ProcessMemoryCollector {
+    fn name(&self) -> &str {
+        "process_memory"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // Linux only
+        if !self.proc_root.join("self/status").is_file() {
+            return Ok(());
+        }
+        let mut largest: Vec<_> = metrics.processes.iter_mut().collect();
+        largest.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid)));
+        for process in largest.into_iter().take(self.count) {
+            // Exited since the process list was read
+            process.memory_detail = read_memory(&self.proc_root, process.pid).ok();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sysinfo::SystemExt;
+    use crate::monitor::ProcessMetrics;
+
+    #[test]
+    fn test_detail_for_largest_only() {
+        let own = std::process::id();
+        let mut metrics = DetailedMetrics {
+            processes: vec![
+                ProcessMetrics { pid: own, memory: 100, ..ProcessMetrics::default() },
+                ProcessMetrics { pid: u32::MAX, memory: 50, ..ProcessMetrics::default() },
+                ProcessMetrics { pid: 1, memory: 10, ..ProcessMetrics::default() },
+            ],
+            ..DetailedMetrics::default()
+        };
+        let mut collector = ProcessMemoryCollector::from_config(&ProcessConfig { detail_count: 2 });
+        collector.collect(&mut System::new(), &mut metrics).unwrap();
+
+        let detail = metrics.processes[0].memory_detail.as_ref().unwrap();
+        assert!(detail.rss > 0 && detail.threads >= 1);
+        assert!(detail.pss.is_some_and(|pss| pss <= detail.rss));
+        // No such process, and outside the top two
+        assert!(metrics.processes[1].memory_detail.is_none());
+        assert!(metrics.processes[2].memory_detail.is_none());
+    }
+}
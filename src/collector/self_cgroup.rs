@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+use sysinfo::System;
+use crate::config::CgroupConfig;
+use crate::error::MonitorError;
+use crate::monitor::{CgroupMetrics, DetailedMetrics};
+use crate::procfs::cgroup::{self, CgroupStats, SELF_CGROUP_PATH};
+use super::Collector;
+
+// Limits, usage and throttling of the cgroup the monitor runs in. Inside
+// a container it also makes the `basic` memory and CPU figures relative
+// to the container's limits, so percentages mean something there.
+pub struct SelfCgroupCollector {
+    root: PathBuf,
+    use_limits: Option<bool>,
+    last_sample: Option<(CgroupStats, Instant)>,
+}
+
+impl SelfCgroupCollector {
+    pub fn new() -> Self {
+        Self::from_config(&CgroupConfig::default())
+    }
+
+    pub fn from_config(config: &CgroupConfig) -> Self {
+        Self {
+            root: PathBuf::from(&config.root),
+            use_limits: config.use_limits,
+            last_sample: None,
+        }
+    }
+}
+
+impl Default for SelfCgroupCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for SelfCgroupCollector {
+    fn name(&self) -> &str {
+        "cgroup"
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // Looked up every time: the monitor can be moved between cgroups
+        let membership = fs::read_to_string(SELF_CGROUP_PATH).unwrap_or_default();
+        let cgroup = match cgroup::detect(&self.root, &membership) {
+            Some(cgroup) => cgroup,
+            None => {
+                metrics.cgroup = None;
+                return Ok(());
+            }
+        };
+        let stats = cgroup::read_stats(&cgroup);
+        let now = Instant::now();
+        let apply = self.use_limits.unwrap_or_else(|| cgroup.is_namespace_root());
+
+        let previous = self.last_sample.as_ref()
+            .map(|(stats, at)| (stats, now.saturating_duration_since(*at).as_secs_f64()));
+        let mut cg = summarize(&cgroup.path, &stats, previous, metrics.basic.total_memory);
+        if apply {
+            cg.limits_applied = apply_limits(metrics, &cg);
+        }
+        metrics.cgroup = Some(cg);
+        self.last_sample = Some((stats, now));
+        Ok(())
+    }
+}
+
+// `previous` is the last sample and the seconds since it was taken;
+// `total_memory` is physical memory, 0 if unknown
+fn summarize(path: &str, stats: &CgroupStats, previous: Option<(&CgroupStats, f64)>, total_memory: u64) -> CgroupMetrics {
+    let working_set = stats.working_set();
+    let cpu_quota_cores = stats.cpu_max.cores();
+    let cpu = &stats.cpu_stat;
+    let mut cg = CgroupMetrics {
+        path: path.to_string(),
+        limits_applied: false,
+        memory_current: stats.memory_current,
+        memory_working_set: working_set,
+        memory_max: stats.memory_max,
+        memory_high: stats.memory_high,
+        memory_usage_percent: stats.memory_max
+            .map(|max| effective_limit(max, total_memory))
+            .filter(|&max| max > 0)
+            .map(|max| working_set as f64 / max as f64 * 100.0),
+        memory_max_events: stats.memory_events.max,
+        oom_events: stats.memory_events.oom,
+        oom_kill_events: stats.memory_events.oom_kill,
+        cpu_quota_cores,
+        cpu_usage_percent: None,
+        cpu_usage_usec: cpu.usage_usec,
+        cpu_nr_periods: cpu.nr_periods,
+        cpu_nr_throttled: cpu.nr_throttled,
+        cpu_throttled_usec: cpu.throttled_usec,
+        cpu_throttled_percent: 0.0,
+    };
+
+    if let Some((prev, elapsed)) = previous.filter(|(_, elapsed)| *elapsed > 0.0) {
+        let used_cores = cpu.usage_usec.saturating_sub(prev.cpu_stat.usage_usec) as f64 / 1e6 / elapsed;
+        cg.cpu_usage_percent = cpu_quota_cores.map(|quota| used_cores / quota * 100.0);
+        let periods = cpu.nr_periods.saturating_sub(prev.cpu_stat.nr_periods);
+        if periods > 0 {
+            let throttled = cpu.nr_throttled.saturating_sub(prev.cpu_stat.nr_throttled);
+            cg.cpu_throttled_percent = throttled as f64 / periods as f64 * 100.0;
+        }
+    }
+    cg
+}
+
+// A limit above physical memory is no limit at all
+fn effective_limit(max: u64, total_memory: u64) -> u64 {
+    if total_memory > 0 { max.min(total_memory) } else { max }
+}
+
+// Rescale `basic` to the limits that are set. Returns whether any was.
+fn apply_limits(metrics: &mut DetailedMetrics, cg: &CgroupMetrics) -> bool {
+    let basic = &mut metrics.basic;
+    let mut applied = false;
+    if let Some(max) = cg.memory_max {
+        basic.total_memory = effective_limit(max, basic.total_memory);
+        basic.used_memory = cg.memory_working_set.min(basic.total_memory);
+        basic.available_memory = basic.total_memory - basic.used_memory;
+        applied = true;
+    }
+    if let Some(usage) = cg.cpu_usage_percent {
+        basic.cpu_usage = usage.clamp(0.0, 100.0) as f32;
+        applied = true;
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::cgroup::{CpuMax, CpuStat};
+
+    #[test]
+    fn test_percentages_against_limits() {
+        let mut stats = CgroupStats {
+            memory_current: 600,
+            inactive_file: 100,
+            memory_max: Some(1000),
+            cpu_max: CpuMax { quota: Some(50_000), period: 100_000 },
+            cpu_stat: CpuStat { usage_usec: 1_000_000, nr_periods: 100, nr_throttled: 10, ..CpuStat::default() },
+            ..CgroupStats::default()
+        };
+        let before = stats.clone();
+        // Half a CPU's worth of quota, a quarter of it used over 2s
+        stats.cpu_stat.usage_usec += 250_000;
+        stats.cpu_stat.nr_periods += 20;
+        stats.cpu_stat.nr_throttled += 5;
+
+        let cg = summarize("/", &stats, Some((&before, 2.0)), 64_000);
+        assert_eq!(cg.memory_usage_percent, Some(50.0));
+        assert_eq!(cg.cpu_usage_percent, Some(25.0));
+        assert_eq!(cg.cpu_throttled_percent, 25.0);
+
+        let mut metrics = DetailedMetrics::default();
+        metrics.basic.total_memory = 64_000;
+        metrics.basic.used_memory = 30_000;
+        assert!(apply_limits(&mut metrics, &cg));
+        assert_eq!((metrics.basic.total_memory, metrics.basic.used_memory, metrics.basic.available_memory), (1000, 500, 500));
+        assert_eq!(metrics.basic.cpu_usage, 25.0);
+
+        // No limits: nothing to rescale
+        let unlimited = summarize("/", &CgroupStats::default(), None, 64_000);
+        assert_eq!(unlimited.memory_usage_percent, None);
+        assert!(!apply_limits(&mut DetailedMetrics::default(), &unlimited));
+
+        // A limit above physical memory: usage is relative to the RAM
+        stats.memory_max = Some(u64::MAX);
+        assert_eq!(summarize("/", &stats, None, 1000).memory_usage_percent, Some(50.0));
+    }
+}
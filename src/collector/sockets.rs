@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use sysinfo::System;
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, ListeningSocket, Peer, SocketMetrics};
+use crate::procfs::sockets::{self, Socket, TcpState, NET_DIR, PROC_ROOT};
+use super::Collector;
+
+const TOP_PEERS: usize = 10;
+
+// Socket counts by state, listening ports with their owners and the
+// busiest peers, from /proc/net/{tcp,tcp6,udp,udp6}
+pub struct SocketCollector {
+    net_dir: PathBuf,
+    proc_root: PathBuf,
+}
+
+impl SocketCollector {
+    pub fn new() -> Self {
+        Self { net_dir: NET_DIR.into(), proc_root: PROC_ROOT.into() }
+    }
+}
+
+impl Default for SocketCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for SocketCollector {
+    fn name(&self) -> &str {
+        "sockets"
+    }
+
+    // Finding the owners walks every process's fd table
+    fn interval(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        let all = sockets::read_all(&self.net_dir);
+        let owners = sockets::socket_owners(&self.proc_root).unwrap_or_default();
+        let names: HashMap<u32, &str> = metrics.processes.iter().map(|p| (p.pid, p.name.as_str())).collect();
+
+        let stats = summarize(&all, &owners, &names);
+        metrics.network.connections = stats.tcp_states.get(TcpState::Established.as_str()).copied().unwrap_or(0);
+        metrics.sockets = stats;
+        Ok(())
+    }
+}
+
+fn summarize(all: &[Socket], owners: &HashMap<u64, u32>, names: &HashMap<u32, &str>) -> SocketMetrics {
+    let mut stats = SocketMetrics::default();
+    let mut peers: HashMap<String, usize> = HashMap::new();
+    for socket in all {
+        if socket.protocol.is_tcp() {
+            *stats.tcp_states.entry(socket.state.as_str().to_string()).or_default() += 1;
+            if socket.state == TcpState::Established {
+                *peers.entry(socket.remote.ip().to_string()).or_default() += 1;
+            }
+        } else {
+            stats.udp_sockets += 1;
+        }
+
+        if socket.is_listening() {
+            let pid = owners.get(&socket.inode).copied();
+            stats.listening.push(ListeningSocket {
+                protocol: socket.protocol.to_string(),
+                address: socket.local.ip().to_string(),
+                port: socket.local.port(),
+                pid,
+                process: pid.and_then(|pid| names.get(&pid)).map(|name| name.to_string()),
+            });
+        }
+    }
+
+    stats.listening.sort_by(|a, b| (a.port, &a.protocol, &a.address).cmp(&(b.port, &b.protocol, &b.address)));
+    let mut peers: Vec<_> = peers.into_iter().map(|(address, connections)| Peer { address, connections }).collect();
+    peers.sort_by(|a, b| b.connections.cmp(&a.connections).then_with(|| a.address.cmp(&b.address)));
+    peers.truncate(TOP_PEERS);
+    stats.top_peers = peers;
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procfs::sockets::Protocol;
+
+    #[test]
+    fn test_summary() {
+        let tcp = sockets::parse(Protocol::Tcp, "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 100 1
+   1: 0F02000A:0016 0202000A:D431 01 00000000:00000000 00:00000000 00000000     0        0 101 1
+   2: 0F02000A:0016 0202000A:D432 01 00000000:00000000 00:00000000 00000000     0        0 102 1
+   3: 0F02000A:0016 0302000A:D433 01 00000000:00000000 00:00000000 00000000     0        0 103 1
+   4: 0F02000A:0016 0302000A:D434 06 00000000:00000000 00:00000000 00000000     0        0 0 1
+");
+        let owners: HashMap<u64, u32> = [(100, 42)].into();
+        let names: HashMap<u32, &str> = [(42, "sshd")].into();
+        let stats = summarize(&tcp, &owners, &names);
+
+        assert_eq!(stats.tcp_states["ESTABLISHED"], 3);
+        assert_eq!(stats.tcp_states["TIME_WAIT"], 1);
+        assert_eq!(stats.listening.len(), 1);
+        assert_eq!((stats.listening[0].port, stats.listening[0].process.as_deref()), (22, Some("sshd")));
+        assert_eq!(stats.top_peers[0].connections, 2);
+        assert_eq!(stats.top_peers.len(), 2);
+    }
+}
@@ -0,0 +1,53 @@
+use sysinfo::{System, SystemExt, CpuExt};
+use crate::error::MonitorError;
+use crate::monitor::{CoreMetrics, DetailedMetrics};
+use super::Collector;
+
+// CPU usage (overall and per core), memory totals and load average
+pub struct SystemCollector {
+    cpu_primed: bool,
+}
+
+impl SystemCollector {
+    pub fn new() -> Self {
+        Self { cpu_primed: false }
+    }
+}
+
+impl Default for SystemCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for SystemCollector {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        // CPU usage is a delta between two refreshes, so the very first
+        // sample needs a short pause; later samples use the previous refresh.
+        if !self.cpu_primed {
+            sys.refresh_cpu();
+            std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+            self.cpu_primed = true;
+        }
+        sys.refresh_cpu();
+        sys.refresh_memory();
+
+        metrics.cpu.cores = sys.cpus().iter().map(|cpu| CoreMetrics {
+            name: cpu.name().to_string(),
+            usage: cpu.cpu_usage(),
+            frequency_mhz: cpu.frequency(),
+        }).collect();
+
+        let basic = &mut metrics.basic;
+        basic.cpu_usage = sys.global_cpu_info().cpu_usage();
+        basic.total_memory = sys.total_memory();
+        basic.used_memory = sys.used_memory();
+        basic.available_memory = sys.available_memory();
+        basic.load_average = sys.load_average().into();
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use sysinfo::{System, SystemExt, ComponentExt};
+use crate::error::MonitorError;
+use crate::monitor::{DetailedMetrics, Temperature};
+use super::Collector;
+
+pub struct TemperatureCollector {
+    listed: bool,
+}
+
+impl TemperatureCollector {
+    pub fn new() -> Self {
+        Self { listed: false }
+    }
+}
+
+impl Default for TemperatureCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for TemperatureCollector {
+    fn name(&self) -> &str {
+        "temperatures"
+    }
+
+    fn collect(&mut self, sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+        if self.listed {
+            sys.refresh_components();
+        } else {
+            sys.refresh_components_list();
+            self.listed = true;
+        }
+
+        metrics.temperatures = sys.components().iter()
+            .map(|component| Temperature {
+                label: component.label().to_string(),
+                value: component.temperature(),
+            }).collect();
+        Ok(())
+    }
+}
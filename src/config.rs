@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
+use crate::alert::{AlertRuleConfig, WebhookConfig};
 
 pub const MEMORY_WARNING_THRESHOLD: f64 = 80.0;
 pub const MEMORY_CRITICAL_THRESHOLD: f64 = 90.0;
@@ -15,16 +17,130 @@ pub struct Config {
     pub max_log_size: u64,
     pub max_backup_files: u32,
     pub display: DisplayConfig,
+    // Per-collector settings keyed by collector name
+    #[serde(default)]
+    pub collectors: BTreeMap<String, CollectorConfig>,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub alerts: Vec<AlertRuleConfig>,
+    // Every alert is POSTed to each of these
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub cgroup: CgroupConfig,
+    #[serde(default)]
+    pub process: ProcessConfig,
+    #[serde(default)]
+    pub leaks: LeakConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DisplayConfig {
-    pub bar_width: usize,
-    pub show_disk_info: bool,
-    pub show_system_load: bool,
+    // Seconds between redraws of the dashboard
     pub refresh_rate: u64,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    // Bearer token for endpoints that change the system (process actions);
+    // those endpoints are disabled while it is unset
+    pub token: Option<String>,
+}
+
+// Which interfaces the network collector reports. Patterns may use `*`
+// as a wildcard; an empty `include` means every interface.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CgroupConfig {
+    // Where cgroup2 is mounted
+    pub root: String,
+    // Report memory and CPU usage against the cgroup's limits instead of
+    // the host's. When unset, only done inside a cgroup namespace, i.e. in
+    // a container rather than a systemd service with a MemoryMax.
+    pub use_limits: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessConfig {
+    // Largest processes (by memory) whose smaps_rollup and status are read
+    // on every sample
+    pub detail_count: usize,
+}
+
+// When a process's memory growth counts as a suspected leak. The trend is
+// a least-squares line through the process's RSS over the last
+// `window_secs`, recorded every `sample_interval_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeakConfig {
+    pub window_secs: u64,
+    pub sample_interval_secs: u64,
+    // Points needed before a process is judged at all
+    pub min_samples: usize,
+    pub min_growth_bytes_per_hour: u64,
+    // How well the line fits (R²), 0 to 1
+    pub min_r_squared: f64,
+    // Share of steps between points where memory did not shrink
+    pub min_monotonic_ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectorConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // Refresh interval in seconds; the collector's own default when unset
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub path: String,
+    // Samples written per transaction
+    pub batch_size: usize,
+    // Largest processes (by memory) stored per sample
+    pub max_processes: usize,
+    pub retention: RetentionConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    // How long raw samples are kept, in seconds
+    pub raw_secs: u64,
+    // Rollup tiers from finest to coarsest; each tier is built from the
+    // one before it (the first from raw samples)
+    pub tiers: Vec<RollupTier>,
+    pub compaction_interval_secs: u64,
+    // Zero disables VACUUM
+    pub vacuum_interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupTier {
+    pub resolution_secs: u64,
+    pub keep_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -34,19 +150,39 @@ impl Default for Config {
             max_log_size: 10 * 1024 * 1024,
             max_backup_files: 5,
             display: DisplayConfig::default(),
+            collectors: BTreeMap::new(),
+            storage: StorageConfig::default(),
+            alerts: Vec::new(),
+            webhooks: Vec::new(),
+            api: ApiConfig::default(),
+            network: NetworkConfig::default(),
+            cgroup: CgroupConfig::default(),
+            process: ProcessConfig::default(),
+            leaks: LeakConfig::default(),
         }
     }
 }
 
 impl Config {
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = "config.json";
-        if !Path::new(config_path).exists() {
+        Self::load_from("config.json")
+    }
+
+    // Reads the config at `config_path`, writing the defaults there first
+    // if the file does not exist yet
+    pub fn load_from<P: AsRef<Path>>(config_path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_path = config_path.as_ref();
+        if !config_path.exists() {
             let config = Config::default();
             let json = serde_json::to_string_pretty(&config)?;
             fs::write(config_path, json)?;
             return Ok(config);
         }
+        Self::read_from(config_path)
+    }
+
+    // Reads the config at `config_path`; a missing file is an error
+    pub fn read_from<P: AsRef<Path>>(config_path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(config_path)?;
         Ok(serde_json::from_str(&content)?)
     }
@@ -55,10 +191,70 @@ impl Config {
 impl Default for DisplayConfig {
     fn default() -> Self {
         Self {
-            bar_width: 50,
-            show_disk_info: true,
-            show_system_load: true,
             refresh_rate: 1,
         }
     }
 }
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: vec!["lo".to_string(), "veth*".to_string()],
+        }
+    }
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        Self {
+            root: "/sys/fs/cgroup".to_string(),
+            use_limits: None,
+        }
+    }
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        Self { detail_count: 20 }
+    }
+}
+
+impl Default for LeakConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 60 * 60,
+            sample_interval_secs: 30,
+            min_samples: 20,
+            min_growth_bytes_per_hour: 10 * 1024 * 1024,
+            min_r_squared: 0.8,
+            min_monotonic_ratio: 0.8,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "metrics.db".to_string(),
+            batch_size: 10,
+            max_processes: 50,
+            retention: RetentionConfig::default(),
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        const DAY: u64 = 24 * 60 * 60;
+        Self {
+            raw_secs: DAY,
+            tiers: vec![
+                RollupTier { resolution_secs: 60, keep_secs: 30 * DAY },
+                RollupTier { resolution_secs: 60 * 60, keep_secs: 365 * DAY },
+            ],
+            compaction_interval_secs: 5 * 60,
+            vacuum_interval_secs: DAY,
+        }
+    }
+}
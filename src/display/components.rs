@@ -1 +1,356 @@
- 
\ No newline at end of file
+use std::time::Duration;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{Axis, Cell, Chart, Dataset, Gauge, Paragraph, Row, Sparkline, Table, Wrap},
+    Frame,
+};
+use crate::monitor::{CgroupUsage, CpuMetrics, DetailedMetrics, LeakSuspect, MetricsHistory, ProcessMetrics};
+use crate::utils::format_bytes;
+use super::styles;
+
+// CPU and memory gauges, the memory breakdown, stall pressure and
+// network totals
+pub fn overview<B: Backend>(f: &mut Frame<B>, area: Rect, metrics: &DetailedMetrics) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(20),
+            Constraint::Percentage(20),
+            Constraint::Percentage(28),
+            Constraint::Percentage(17),
+            Constraint::Percentage(15),
+        ].as_ref())
+        .split(area);
+
+    // Relative to the container's limits rather than the host
+    let scoped = metrics.cgroup.as_ref().is_some_and(|cg| cg.limits_applied);
+    let cpu = metrics.basic.cpu_usage as f64;
+    let cpu_gauge = Gauge::default()
+        .block(styles::block(if scoped { "CPU Usage (cgroup)" } else { "CPU Usage" }))
+        .gauge_style(styles::usage_style(cpu, styles::CPU_COLOR))
+        .percent(percent(cpu));
+    f.render_widget(cpu_gauge, chunks[0]);
+
+    let memory = if metrics.basic.total_memory > 0 {
+        metrics.basic.used_memory as f64 / metrics.basic.total_memory as f64 * 100.0
+    } else {
+        0.0
+    };
+    let memory_gauge = Gauge::default()
+        .block(styles::block(if scoped { "Memory Usage (cgroup)" } else { "Memory Usage" }))
+        .gauge_style(styles::usage_style(memory, styles::MEMORY_COLOR))
+        .label(format!(
+            "{} / {}",
+            format_bytes(metrics.basic.used_memory),
+            format_bytes(metrics.basic.total_memory)
+        ))
+        .percent(percent(memory));
+    f.render_widget(memory_gauge, chunks[1]);
+    memory_detail(f, chunks[2], metrics);
+    pressure(f, chunks[3], metrics);
+
+    let network = &metrics.network;
+    let network_info = Paragraph::new(format!(
+        "RX: {}/s\nTX: {}/s\nTotal RX: {}\nTotal TX: {}",
+        format_bytes(network.rx_bytes_per_sec as u64),
+        format_bytes(network.tx_bytes_per_sec as u64),
+        format_bytes(network.rx_bytes),
+        format_bytes(network.tx_bytes),
+    ))
+    .block(styles::block("Network"));
+    f.render_widget(network_info, chunks[4]);
+}
+
+fn memory_detail<B: Backend>(f: &mut Frame<B>, area: Rect, metrics: &DetailedMetrics) {
+    let m = &metrics.memory;
+    let mut lines = vec![
+        format!("Swap {} / {}  in {:.0}/s out {:.0}/s",
+            format_bytes(m.swap_used), format_bytes(m.swap_total), m.swap_in_pages_per_sec, m.swap_out_pages_per_sec),
+        format!("Cache {}  Buffers {}  Shared {}",
+            format_bytes(m.cached), format_bytes(m.buffers), format_bytes(m.shared)),
+        format!("Slab {}  Dirty {}  Writeback {}",
+            format_bytes(m.slab), format_bytes(m.dirty), format_bytes(m.writeback)),
+        format!("Committed {} / {}  Major faults {:.0}/s",
+            format_bytes(m.committed_as), format_bytes(m.commit_limit), m.major_faults_per_sec),
+    ];
+    if m.hugepages_total > 0 {
+        lines.push(format!("Huge pages {} free of {} x {}",
+            m.hugepages_free, m.hugepages_total, format_bytes(m.hugepage_size)));
+    }
+    let text: Vec<Spans> = lines.into_iter().map(Spans::from).collect();
+    f.render_widget(Paragraph::new(text).block(styles::block("Memory Detail")), area);
+}
+
+// The 10s averages; memory and I/O also show the "full" line, where every
+// task was stalled at once
+fn pressure<B: Backend>(f: &mut Frame<B>, area: Rect, metrics: &DetailedMetrics) {
+    let psi = &metrics.pressure;
+    let block = styles::block("Pressure 10s");
+    if !psi.is_available() {
+        f.render_widget(Paragraph::new("Not available").block(block), area);
+        return;
+    }
+
+    let rows = [("cpu", psi.cpu, false), ("mem", psi.memory, true), ("io", psi.io, true)];
+    let mut lines = vec![Spans::from("     some   full")];
+    for (label, pressure, has_full) in rows {
+        let line = match pressure {
+            Some(p) => {
+                let full = if has_full { format!("{:>5.1}%", p.full.avg10) } else { String::new() };
+                Spans::from(vec![
+                    Span::raw(format!("{:<4}", label)),
+                    Span::styled(format!("{:>5.1}%", p.some.avg10), styles::pressure_style(p.some.avg10)),
+                    Span::raw(" "),
+                    Span::styled(full, styles::pressure_style(p.full.avg10)),
+                ])
+            }
+            None => Spans::from(format!("{:<4}    -", label)),
+        };
+        lines.push(line);
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// Width of one core in the grid: "cpu12 [||||||    ] 100% 3.20G  "
+const CORE_CELL_WIDTH: u16 = 31;
+const CORE_BAR_WIDTH: usize = 10;
+// Past this the grid scrolls off rather than squeezing the other panels
+const MAX_CORE_ROWS: usize = 8;
+
+// Height of the core grid for `width` columns: the breakdown line, one
+// line per row of cores and the borders
+pub fn cpu_cores_height(cpu: &CpuMetrics, width: u16) -> u16 {
+    let rows = cpu.cores.len().div_ceil(core_columns(width)).min(MAX_CORE_ROWS);
+    rows as u16 + 3
+}
+
+fn core_columns(width: u16) -> usize {
+    (width.saturating_sub(2) / CORE_CELL_WIDTH).max(1) as usize
+}
+
+// Time breakdown on top, then a usage bar and frequency per core
+pub fn cpu_cores<B: Backend>(f: &mut Frame<B>, area: Rect, cpu: &CpuMetrics) {
+    let b = &cpu.breakdown;
+    let mut lines = vec![Spans::from(format!(
+        "user {:.1}%  nice {:.1}%  sys {:.1}%  iowait {:.1}%  steal {:.1}%  irq {:.1}%  softirq {:.1}%  idle {:.1}%",
+        b.user, b.nice, b.system, b.iowait, b.steal, b.irq, b.softirq, b.idle,
+    ))];
+
+    for row in cpu.cores.chunks(core_columns(area.width)) {
+        let mut spans = Vec::new();
+        for core in row {
+            let usage = core.usage.clamp(0.0, 100.0) as f64;
+            let filled = (usage / 100.0 * CORE_BAR_WIDTH as f64).round() as usize;
+            spans.push(Span::raw(format!("{:<6}[", core.name)));
+            spans.push(Span::styled("|".repeat(filled), styles::usage_style(usage, styles::CPU_COLOR)));
+            spans.push(Span::raw(format!(
+                "{}] {:>3.0}% {:>5}  ",
+                " ".repeat(CORE_BAR_WIDTH - filled),
+                usage,
+                format_frequency(core.frequency_mhz),
+            )));
+        }
+        lines.push(Spans::from(spans));
+    }
+
+    f.render_widget(Paragraph::new(lines).block(styles::block("CPU Cores")), area);
+}
+
+fn format_frequency(mhz: u64) -> String {
+    match mhz {
+        0 => String::new(),
+        1..=999 => format!("{}M", mhz),
+        _ => format!("{:.2}G", mhz as f64 / 1000.0),
+    }
+}
+
+// CPU and memory history as percentages, next to per-interface
+// throughput sparklines
+pub fn history_charts<B: Backend>(f: &mut Frame<B>, area: Rect, history: &MetricsHistory, metrics: &DetailedMetrics) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(35), Constraint::Percentage(35), Constraint::Percentage(30)].as_ref())
+        .split(area);
+    let cpu = history.get_cpu_data();
+    let memory = history.get_memory_data();
+
+    let charts = [("CPU History", &cpu, styles::CPU_COLOR), ("Memory History", &memory, styles::MEMORY_COLOR)];
+    for ((title, data, color), chunk) in charts.into_iter().zip(chunks.iter().copied()) {
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(color))
+            .data(data);
+        let chart = Chart::new(vec![dataset])
+            .block(styles::block(title))
+            .x_axis(Axis::default().bounds([0.0, 100.0]))
+            .y_axis(Axis::default().bounds([0.0, 100.0]));
+        f.render_widget(chart, chunk);
+    }
+    network_sparklines(f, chunks[2], history, metrics);
+}
+
+// Two lines per interface: current rates, then rx+tx throughput history
+fn network_sparklines<B: Backend>(f: &mut Frame<B>, area: Rect, history: &MetricsHistory, metrics: &DetailedMetrics) {
+    let block = styles::block("Network");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let slots = (inner.height / 2) as usize;
+    for (i, interface) in metrics.network.interfaces.iter().take(slots).enumerate() {
+        let y = inner.y + 2 * i as u16;
+        let label = format!(
+            "{} ↓{}/s ↑{}/s",
+            interface.name,
+            format_bytes(interface.rx_bytes_per_sec as u64),
+            format_bytes(interface.tx_bytes_per_sec as u64),
+        );
+        f.render_widget(Paragraph::new(label), Rect { y, height: 1, ..inner });
+
+        // Only the newest points that fit the width
+        let data = history.get_network_data(&interface.name);
+        let data = &data[data.len().saturating_sub(inner.width as usize)..];
+        let sparkline = Sparkline::default().data(data).style(Style::default().fg(styles::NETWORK_COLOR));
+        f.render_widget(sparkline, Rect { y: y + 1, height: 1, ..inner });
+    }
+}
+
+pub struct Status<'a> {
+    pub interval: Duration,
+    pub paused: bool,
+    pub filtering: bool,
+    // Shown instead of the key help, e.g. a confirmation prompt
+    pub message: Option<&'a str>,
+}
+
+// One-line key help with the sampling interval and pause state
+pub fn status_bar<B: Backend>(f: &mut Frame<B>, area: Rect, status: &Status) {
+    if let Some(message) = status.message {
+        let line = Span::styled(format!(" {}", message), styles::highlight_style());
+        f.render_widget(Paragraph::new(Spans::from(line)).style(styles::status_style()), area);
+        return;
+    }
+    let keys: &[(&str, &str)] = if status.filtering {
+        &[("Enter", "Keep filter"), ("Esc", "Clear filter")]
+    } else {
+        &[
+            ("q", "Quit"),
+            ("p", "Pause"),
+            ("+/-", "Interval"),
+            ("/", "Filter"),
+            ("c/m/n/i/s", "Sort"),
+            ("t", "Tree"),
+            ("Enter", "Detail"),
+            ("g", "Groups"),
+            ("k/K", "Term/Kill"),
+            ("z/Z", "Stop/Cont"),
+            ("[/]", "Nice"),
+            ("↑↓", "Scroll"),
+        ]
+    };
+    let mut spans = Vec::new();
+    for (key, label) in keys {
+        spans.push(Span::styled(format!(" {} ", key), styles::key_style()));
+        spans.push(Span::raw(format!(" {}  ", label)));
+    }
+    spans.push(Span::raw(format!("every {:?}", status.interval)));
+    if status.paused {
+        spans.push(Span::styled("  PAUSED", styles::highlight_style()));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)).style(styles::status_style()), area);
+}
+
+// Workload cgroups (containers, pods, services, scopes) by memory. Process
+// counts include child cgroups, so pods count their containers' processes.
+pub fn cgroup_table<B: Backend>(f: &mut Frame<B>, area: Rect, cgroups: &[CgroupUsage]) {
+    let workloads: Vec<_> = cgroups.iter().filter(|cg| cg.kind.is_workload()).collect();
+    let header = Row::new(["Kind", "Name", "Memory", "Limit", "CPU%", "Read/s", "Write/s", "Procs"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let body = workloads.iter().map(|cg| {
+        let prefix = format!("{}/", cg.path);
+        let processes = cg.pids.len() + cgroups.iter()
+            .filter(|other| other.path.starts_with(&prefix))
+            .map(|other| other.pids.len())
+            .sum::<usize>();
+        Row::new(vec![
+            Cell::from(cg.kind.as_str()),
+            Cell::from(cg.name.clone()),
+            Cell::from(format_bytes(cg.memory_current)),
+            Cell::from(cg.memory_max.map(format_bytes).unwrap_or_else(|| "-".to_string())),
+            Cell::from(format!("{:.1}", cg.cpu_usage)),
+            Cell::from(format_bytes(cg.io_read_bytes_per_sec as u64)),
+            Cell::from(format_bytes(cg.io_write_bytes_per_sec as u64)),
+            Cell::from(processes.to_string()),
+        ])
+    });
+
+    let title = if cgroups.is_empty() {
+        "Cgroups (needs cgroup v2)".to_string()
+    } else {
+        format!("Cgroups ({} workloads)", workloads.len())
+    };
+    let widths = [
+        Constraint::Length(10),
+        Constraint::Percentage(35),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(7),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(6),
+    ];
+    let table = Table::new(body).header(header).block(styles::block(&title)).widths(&widths);
+    f.render_widget(table, area);
+}
+
+// Memory breakdown of the selected process, and its growth if it is a
+// suspected leak
+pub fn process_detail<B: Backend>(f: &mut Frame<B>, area: Rect, process: Option<&ProcessMetrics>, leak: Option<&LeakSuspect>) {
+    let process = match process {
+        Some(process) => process,
+        None => {
+            f.render_widget(Paragraph::new("No process selected").block(styles::block("Process")), area);
+            return;
+        }
+    };
+    let optional = |value: Option<u64>| value.map_or_else(|| "n/a".to_string(), format_bytes);
+    let mut lines = vec![
+        format!("User {}", process.user.as_deref().unwrap_or("?")),
+        format!("Cgroup {}", process.cgroup.as_deref().unwrap_or("-")),
+        String::new(),
+    ];
+    match &process.memory_detail {
+        Some(m) => lines.extend([
+            format!("RSS  {:>11}  peak {}", format_bytes(m.rss), format_bytes(m.peak_rss)),
+            format!("PSS  {:>11}  USS {}", optional(m.pss), optional(m.uss)),
+            format!("Shared {:>9}  Shmem {}", optional(m.shared), format_bytes(m.shmem)),
+            format!("Anon {:>11}  File {}", format_bytes(m.anonymous), format_bytes(m.file_backed)),
+            format!("Swap {:>11}  (PSS {})", format_bytes(m.swap), optional(m.swap_pss)),
+            format!("Threads {}", m.threads),
+        ]),
+        None => lines.push("Memory detail not available".to_string()),
+    }
+    lines.push(String::new());
+    lines.push(process.command.clone());
+
+    let title = format!("{} ({})", process.name, process.pid);
+    let mut text: Vec<Spans> = lines.into_iter().map(Spans::from).collect();
+    if let Some(leak) = leak {
+        let growth = format!("Suspected leak: +{}/h over {} min (R² {:.2})",
+            format_bytes(leak.growth_bytes_per_hour as u64), leak.observed_secs / 60, leak.r_squared);
+        text.insert(2, Spans::from(Span::styled(growth, styles::leak_style())));
+    }
+    f.render_widget(Paragraph::new(text).block(styles::block(&title)).wrap(Wrap { trim: false }), area);
+}
+
+pub fn placeholder<B: Backend>(f: &mut Frame<B>, area: Rect, text: &str) {
+    f.render_widget(Paragraph::new(text.to_string()).block(styles::block("Memory Monitor")), area);
+}
+
+// tui's Gauge panics outside 0..=100
+fn percent(value: f64) -> u16 {
+    value.clamp(0.0, 100.0) as u16
+}
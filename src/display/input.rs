@@ -0,0 +1,104 @@
+use std::time::Duration;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::process::ProcessAction;
+
+// Sampling intervals `+` and `-` step through
+const INTERVAL_STEPS: [Duration; 9] = [
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(300),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    TogglePause,
+    // Processes grouped by cgroup instead of the process table
+    ToggleGroups,
+    // Memory breakdown of the selected process beside the table
+    ToggleDetail,
+    // `+` samples less often, `-` more often
+    SlowerInterval,
+    FasterInterval,
+}
+
+// Actions on the selected process; all of them are confirmed first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessKey {
+    Send(ProcessAction),
+    // Relative to the current nice value; positive lowers the priority
+    Nice(i32),
+}
+
+pub fn process_key_for(key: &KeyEvent) -> Option<ProcessKey> {
+    if key.kind == KeyEventKind::Release {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char('k') => Some(ProcessKey::Send(ProcessAction::Term)),
+        KeyCode::Char('K') => Some(ProcessKey::Send(ProcessAction::Kill)),
+        KeyCode::Char('z') => Some(ProcessKey::Send(ProcessAction::Stop)),
+        KeyCode::Char('Z') => Some(ProcessKey::Send(ProcessAction::Cont)),
+        KeyCode::Char(']') => Some(ProcessKey::Nice(1)),
+        KeyCode::Char('[') => Some(ProcessKey::Nice(-1)),
+        _ => None,
+    }
+}
+
+pub fn action_for(key: &KeyEvent) -> Option<Action> {
+    if key.kind == KeyEventKind::Release {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        KeyCode::Char('p') | KeyCode::Char(' ') => Some(Action::TogglePause),
+        KeyCode::Char('g') => Some(Action::ToggleGroups),
+        KeyCode::Enter => Some(Action::ToggleDetail),
+        KeyCode::Char('+') | KeyCode::Char('=') => Some(Action::SlowerInterval),
+        KeyCode::Char('-') => Some(Action::FasterInterval),
+        _ => None,
+    }
+}
+
+// The next step up or down from `current`, which need not be a step itself
+pub fn next_interval(current: Duration, slower: bool) -> Duration {
+    if slower {
+        INTERVAL_STEPS.iter().copied().find(|&step| step > current).unwrap_or(INTERVAL_STEPS[INTERVAL_STEPS.len() - 1])
+    } else {
+        INTERVAL_STEPS.iter().rev().copied().find(|&step| step < current).unwrap_or(INTERVAL_STEPS[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_and_interval_steps() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert_eq!(action_for(&key(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(action_for(&key(KeyCode::Char('c'), KeyModifiers::NONE)), None);
+        assert_eq!(action_for(&key(KeyCode::Esc, KeyModifiers::NONE)), Some(Action::Quit));
+        assert_eq!(action_for(&key(KeyCode::Char('+'), KeyModifiers::SHIFT)), Some(Action::SlowerInterval));
+        assert_eq!(action_for(&key(KeyCode::Enter, KeyModifiers::NONE)), Some(Action::ToggleDetail));
+        assert_eq!(
+            process_key_for(&key(KeyCode::Char('K'), KeyModifiers::SHIFT)),
+            Some(ProcessKey::Send(ProcessAction::Kill))
+        );
+        assert_eq!(process_key_for(&key(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+
+        let secs = Duration::from_secs;
+        assert_eq!(next_interval(secs(1), true), secs(2));
+        assert_eq!(next_interval(secs(1), false), Duration::from_millis(500));
+        assert_eq!(next_interval(secs(3), false), secs(2));
+        assert_eq!(next_interval(Duration::from_millis(100), false), Duration::from_millis(250));
+        assert_eq!(next_interval(secs(300), true), secs(300));
+    }
+}
@@ -1,5 +1,8 @@
 mod tui;
 mod components;
+mod input;
+mod process_table;
 mod styles;
 
-pub use self::tui::*; 
\ No newline at end of file
+pub use self::process_table::{ProcessTable, SortColumn};
+pub use self::tui::*;
@@ -0,0 +1,413 @@
+use std::cmp::Ordering;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    widgets::{Cell, Row, Table, TableState},
+    Frame,
+};
+use crate::monitor::{LeakSuspect, ProcessMetrics};
+use crate::process::{build_tree, filter_tree, flatten, sort_tree, ProcessNode};
+use crate::utils::format_bytes;
+use super::styles;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    Pid,
+    Name,
+    Cpu,
+    Memory,
+    Status,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 5] = [SortColumn::Pid, SortColumn::Name, SortColumn::Cpu, SortColumn::Memory, SortColumn::Status];
+
+    fn title(self) -> &'static str {
+        match self {
+            SortColumn::Pid => "PID",
+            SortColumn::Name => "Name",
+            SortColumn::Cpu => "CPU%",
+            SortColumn::Memory => "Memory",
+            SortColumn::Status => "Status",
+        }
+    }
+
+    // Numbers read best largest first, text alphabetically
+    fn descending_by_default(self) -> bool {
+        matches!(self, SortColumn::Cpu | SortColumn::Memory)
+    }
+
+    fn compare(self, a: &Line, b: &Line) -> Ordering {
+        match self {
+            SortColumn::Pid => a.pid.cmp(&b.pid),
+            SortColumn::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortColumn::Cpu => a.cpu_usage.total_cmp(&b.cpu_usage),
+            SortColumn::Memory => a.memory.cmp(&b.memory),
+            SortColumn::Status => a.status.cmp(b.status),
+        }
+    }
+}
+
+// What a table row shows; in tree mode CPU and memory are subtree totals
+struct Line<'a> {
+    pid: u32,
+    prefix: String,
+    name: &'a str,
+    cpu_usage: f32,
+    memory: u64,
+    status: &'a str,
+}
+
+impl<'a> Line<'a> {
+    fn process(p: &'a ProcessMetrics) -> Self {
+        Self { pid: p.pid, prefix: String::new(), name: &p.name, cpu_usage: p.cpu_usage, memory: p.memory, status: &p.status }
+    }
+
+    fn node(node: &'a ProcessNode) -> Self {
+        Self {
+            cpu_usage: node.total_cpu_usage,
+            memory: node.total_memory,
+            ..Self::process(&node.process)
+        }
+    }
+}
+
+// Sort order, filter and selection of the TUI process list. The selection
+// follows its process across refreshes and re-sorts.
+pub struct ProcessTable {
+    sort: SortColumn,
+    descending: bool,
+    filter: String,
+    // Typing goes to the filter instead of the key bindings
+    editing_filter: bool,
+    tree: bool,
+    selected: usize,
+    selected_pid: Option<u32>,
+    // Row pids and page height as of the last render
+    visible: Vec<u32>,
+    page_size: usize,
+    state: TableState,
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self {
+            sort: SortColumn::Cpu,
+            descending: true,
+            filter: String::new(),
+            editing_filter: false,
+            tree: false,
+            selected: 0,
+            selected_pid: None,
+            visible: Vec::new(),
+            page_size: 10,
+            state: TableState::default(),
+        }
+    }
+
+    pub fn is_editing_filter(&self) -> bool {
+        self.editing_filter
+    }
+
+    pub fn selected_pid(&self) -> Option<u32> {
+        self.selected_pid
+    }
+
+    pub fn is_tree(&self) -> bool {
+        self.tree
+    }
+
+    // Processes whose name or cgroup contains the filter (case-insensitively), in
+    // display order. Ties keep pid order so rows do not jitter.
+    pub fn rows<'a>(&self, processes: &'a [ProcessMetrics]) -> Vec<&'a ProcessMetrics> {
+        let mut rows: Vec<_> = processes.iter().filter(|p| self.matches(p)).collect();
+        rows.sort_by(|a, b| self.order(&Line::process(a), &Line::process(b)));
+        rows
+    }
+
+    fn matches(&self, process: &ProcessMetrics) -> bool {
+        let filter = self.filter.to_lowercase();
+        filter.is_empty()
+            || process.name.to_lowercase().contains(&filter)
+            || process.cgroup.as_ref().is_some_and(|cgroup| cgroup.to_lowercase().contains(&filter))
+    }
+
+    fn order(&self, a: &Line, b: &Line) -> Ordering {
+        let order = self.sort.compare(a, b);
+        let order = if self.descending { order.reverse() } else { order };
+        order.then(a.pid.cmp(&b.pid))
+    }
+
+    // The tree sorts siblings, and the filter keeps the ancestors of matches
+    fn tree_nodes(&self, processes: &[ProcessMetrics]) -> Vec<ProcessNode> {
+        let mut nodes = filter_tree(build_tree(processes), &|p: &ProcessMetrics| self.matches(p));
+        sort_tree(&mut nodes, |a, b| self.order(&Line::node(a), &Line::node(b)));
+        nodes
+    }
+
+    // Returns whether the table used the key
+    pub fn handle_key(&mut self, key: &KeyEvent) -> bool {
+        // Terminals with keyboard enhancements also report releases
+        if key.kind == KeyEventKind::Release {
+            return false;
+        }
+        if self.editing_filter {
+            return self.edit_filter(key);
+        }
+        match key.code {
+            KeyCode::Char('/') => self.editing_filter = true,
+            KeyCode::Char('t') => self.tree = !self.tree,
+            KeyCode::Char('i') => self.sort_by(SortColumn::Pid),
+            KeyCode::Char('n') => self.sort_by(SortColumn::Name),
+            KeyCode::Char('c') if !key.modifiers.contains(KeyModifiers::CONTROL) => self.sort_by(SortColumn::Cpu),
+            KeyCode::Char('m') => self.sort_by(SortColumn::Memory),
+            KeyCode::Char('s') => self.sort_by(SortColumn::Status),
+            KeyCode::Char('<') | KeyCode::Char('>') => {
+                let index = SortColumn::ALL.iter().position(|&c| c == self.sort).unwrap_or(0);
+                let len = SortColumn::ALL.len();
+                let next = if key.code == KeyCode::Char('>') { index + 1 } else { index + len - 1 };
+                self.sort_by(SortColumn::ALL[next % len]);
+            }
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(self.page_size as isize)),
+            KeyCode::PageDown => self.move_selection(self.page_size as isize),
+            KeyCode::Home => self.move_selection(isize::MIN / 2),
+            KeyCode::End => self.move_selection(isize::MAX / 2),
+            _ => return false,
+        }
+        true
+    }
+
+    // Suspected leaks are flagged after the name
+    pub fn render<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect, processes: &[ProcessMetrics], leaks: &[LeakSuspect]) {
+        let nodes;
+        let rows: Vec<Line> = if self.tree {
+            nodes = self.tree_nodes(processes);
+            flatten(&nodes)
+                .into_iter()
+                .map(|row| Line { prefix: row.prefix, ..Line::node(row.node) })
+                .collect()
+        } else {
+            self.rows(processes).into_iter().map(Line::process).collect()
+        };
+        self.visible = rows.iter().map(|p| p.pid).collect();
+        // Borders and header take three lines
+        self.page_size = (area.height as usize).saturating_sub(3).max(1);
+        self.sync_selection();
+
+        let header = Row::new(SortColumn::ALL.iter().map(|&column| {
+            let title = match (column == self.sort, self.descending) {
+                (true, true) => format!("{}▼", column.title()),
+                (true, false) => format!("{}▲", column.title()),
+                (false, _) => column.title().to_string(),
+            };
+            Cell::from(title)
+        }))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+        let body = rows.iter().map(|p| {
+            let name = if leaks.iter().any(|leak| leak.pid == p.pid) {
+                Cell::from(format!("{}{} [leak?]", p.prefix, p.name)).style(styles::leak_style())
+            } else {
+                Cell::from(format!("{}{}", p.prefix, p.name))
+            };
+            Row::new(vec![
+                Cell::from(p.pid.to_string()),
+                name,
+                Cell::from(format!("{:.1}", p.cpu_usage)),
+                Cell::from(format_bytes(p.memory)),
+                Cell::from(p.status.to_string()),
+            ])
+        });
+
+        let mut title = format!("Processes ({}/{})", rows.len(), processes.len());
+        if self.tree {
+            title.push_str(" tree, subtree totals");
+        }
+        if self.editing_filter {
+            title.push_str(&format!(" filter: {}_", self.filter));
+        } else if !self.filter.is_empty() {
+            title.push_str(&format!(" filter: {}", self.filter));
+        }
+
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Percentage(40),
+            Constraint::Length(7),
+            Constraint::Length(12),
+            Constraint::Length(10),
+        ];
+        let table = Table::new(body)
+            .header(header)
+            .block(styles::block(&title))
+            .widths(&widths)
+            .highlight_style(styles::highlight_style().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(table, area, &mut self.state);
+    }
+
+    fn sort_by(&mut self, column: SortColumn) {
+        if self.sort == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            self.descending = column.descending_by_default();
+        }
+    }
+
+    fn edit_filter(&mut self, key: &KeyEvent) -> bool {
+        match key.code {
+            // Ctrl-C still quits
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char(c) => self.filter.push(c),
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Enter => self.editing_filter = false,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.editing_filter = false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let last = self.visible.len() - 1;
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+        self.selected_pid = Some(self.visible[self.selected]);
+        self.state.select(Some(self.selected));
+    }
+
+    // Re-find the selected process after the rows changed
+    fn sync_selection(&mut self) {
+        if self.visible.is_empty() {
+            self.state.select(None);
+            return;
+        }
+        if let Some(index) = self.selected_pid.and_then(|pid| self.visible.iter().position(|&p| p == pid)) {
+            self.selected = index;
+        }
+        self.selected = self.selected.min(self.visible.len() - 1);
+        self.selected_pid = Some(self.visible[self.selected]);
+        self.state.select(Some(self.selected));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, cpu_usage: f32, memory: u64) -> ProcessMetrics {
+        ProcessMetrics {
+            pid,
+            name: name.to_string(),
+            cpu_usage,
+            memory,
+            status: "Sleeping".to_string(),
+            ..ProcessMetrics::default()
+        }
+    }
+
+    fn press(table: &mut ProcessTable, code: KeyCode) -> bool {
+        table.handle_key(&KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn pids(table: &ProcessTable, processes: &[ProcessMetrics]) -> Vec<u32> {
+        table.rows(processes).iter().map(|p| p.pid).collect()
+    }
+
+    #[test]
+    fn test_sorting_and_filtering() {
+        let processes = vec![
+            process(1, "systemd", 0.5, 300),
+            process(2, "Firefox", 20.0, 900),
+            process(3, "firefox-helper", 20.0, 100),
+            process(4, "bash", 1.0, 50),
+        ];
+        let mut table = ProcessTable::new();
+        // CPU descending, ties by pid
+        assert_eq!(pids(&table, &processes), vec![2, 3, 4, 1]);
+
+        press(&mut table, KeyCode::Char('m'));
+        assert_eq!(pids(&table, &processes), vec![2, 1, 3, 4]);
+        press(&mut table, KeyCode::Char('m'));
+        assert_eq!(pids(&table, &processes), vec![4, 3, 1, 2]);
+        press(&mut table, KeyCode::Char('n'));
+        assert_eq!(pids(&table, &processes), vec![4, 2, 3, 1]);
+
+        press(&mut table, KeyCode::Char('/'));
+        for c in "FIRE".chars() {
+            press(&mut table, KeyCode::Char(c));
+            let release = KeyEvent { kind: KeyEventKind::Release, ..KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE) };
+            assert!(!table.handle_key(&release));
+        }
+        // While typing, sort keys are part of the filter
+        assert!(table.is_editing_filter());
+        assert_eq!(pids(&table, &processes), vec![2, 3]);
+        press(&mut table, KeyCode::Enter);
+        assert_eq!(pids(&table, &processes), vec![2, 3]);
+        press(&mut table, KeyCode::Char('/'));
+        press(&mut table, KeyCode::Esc);
+        assert_eq!(pids(&table, &processes).len(), 4);
+        assert!(!press(&mut table, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn test_tree_mode_sorts_subtrees_by_totals() {
+        let mut processes = vec![
+            process(1, "init", 0.0, 10),
+            process(2, "small", 0.0, 200),
+            process(3, "shell", 0.0, 100),
+            process(4, "build", 0.0, 500),
+        ];
+        processes[1].parent_pid = Some(1);
+        processes[2].parent_pid = Some(1);
+        processes[3].parent_pid = Some(3);
+
+        let mut table = ProcessTable::new();
+        press(&mut table, KeyCode::Char('t'));
+        press(&mut table, KeyCode::Char('m'));
+        let nodes = table.tree_nodes(&processes);
+        // shell is only 100 itself, but 600 with its child
+        let order: Vec<_> = flatten(&nodes).iter().map(|r| r.node.process.pid).collect();
+        assert_eq!(order, vec![1, 3, 4, 2]);
+
+        table.filter = "build".to_string();
+        let order: Vec<_> = flatten(&table.tree_nodes(&processes)).iter().map(|r| r.node.process.pid).collect();
+        assert_eq!(order, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_selection_is_clamped_and_follows_pid() {
+        let mut table = ProcessTable::new();
+        table.visible = vec![10, 20, 30];
+        press(&mut table, KeyCode::End);
+        assert_eq!(table.selected_pid(), Some(30));
+        press(&mut table, KeyCode::PageDown);
+        assert_eq!(table.selected_pid(), Some(30));
+        press(&mut table, KeyCode::Up);
+        assert_eq!(table.selected_pid(), Some(20));
+
+        // Re-sorted rows: the selection moves with process 20
+        table.visible = vec![20, 30, 10];
+        table.sync_selection();
+        assert_eq!(table.selected, 0);
+        // It exited: the index is kept, clamped to the rows left
+        table.visible = vec![30];
+        table.sync_selection();
+        assert_eq!(table.selected_pid(), Some(30));
+    }
+}
@@ -1 +1,54 @@
- 
\ No newline at end of file
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders};
+use crate::config::{MEMORY_CRITICAL_THRESHOLD, MEMORY_WARNING_THRESHOLD};
+
+pub const CPU_COLOR: Color = Color::Cyan;
+pub const MEMORY_COLOR: Color = Color::Magenta;
+pub const NETWORK_COLOR: Color = Color::Green;
+pub const STATUS_BG: Color = Color::DarkGray;
+
+pub fn block(title: &str) -> Block<'_> {
+    Block::default().title(title.to_string()).borders(Borders::ALL)
+}
+
+// `base` while the usage is normal, yellow and red past the warning and
+// critical thresholds
+pub fn usage_style(percent: f64, base: Color) -> Style {
+    let color = if percent >= MEMORY_CRITICAL_THRESHOLD {
+        Color::Red
+    } else if percent >= MEMORY_WARNING_THRESHOLD {
+        Color::Yellow
+    } else {
+        base
+    };
+    Style::default().fg(color)
+}
+
+// Stall percentages: any sustained stall is worth a look, a quarter of
+// the time is serious
+pub fn pressure_style(percent: f64) -> Style {
+    let color = if percent >= 25.0 {
+        Color::Red
+    } else if percent >= 5.0 {
+        Color::Yellow
+    } else {
+        Color::Reset
+    };
+    Style::default().fg(color)
+}
+
+pub fn leak_style() -> Style {
+    Style::default().fg(Color::Red)
+}
+
+pub fn key_style() -> Style {
+    Style::default().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD)
+}
+
+pub fn status_style() -> Style {
+    Style::default().fg(Color::White).bg(STATUS_BG)
+}
+
+pub fn highlight_style() -> Style {
+    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+}
@@ -1,22 +1,57 @@
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    symbols,
-    widgets::{Axis, Block, Borders, Chart, Dataset, Gauge, Paragraph},
     Terminal,
 };
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    cursor::Show,
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::io;
-use crate::monitor::{DetailedMetrics, MetricsHistory};
+use std::panic;
+use std::path::Path;
+use std::sync::Once;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::TryRecvError;
+use crate::monitor::{DetailedMetrics, MetricsHistory, ProcessMemory};
+use crate::process::{nice, perform_audited, read_memory_of, ProcessAction, NICE_MAX, NICE_MIN, PROC_ROOT};
+use crate::sampler::SamplerHandle;
+use super::components::{self, Status};
+use super::input::{action_for, next_interval, process_key_for, Action, ProcessKey};
+use super::process_table::ProcessTable;
+
+// Upper bound on how long a key press waits to be handled
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+static PANIC_HOOK: Once = Once::new();
+
+// A process action waiting for the user to confirm it
+struct PendingAction {
+    pid: u32,
+    name: String,
+    action: ProcessAction,
+}
 
 pub struct Dashboard {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
     history: MetricsHistory,
+    latest: Option<DetailedMetrics>,
+    processes: ProcessTable,
+    pending: Option<PendingAction>,
+    // Outcome of the last action, shown until the next key press
+    notice: Option<String>,
+    // Cgroup view in place of the process table
+    grouped: bool,
+    // Selected process's memory breakdown beside the process table
+    detail: bool,
+    // Breakdown read for the selected process (pid, start time) when the
+    // collector skipped it; dropped with every new sample
+    detail_cache: Option<((u32, u64), Option<ProcessMemory>)>,
+    paused: bool,
+    // Between init and cleanup; Drop restores the terminal if set
+    active: bool,
 }
 
 impl Dashboard {
@@ -25,11 +60,25 @@ impl Dashboard {
         let terminal = Terminal::new(backend)?;
         let history = MetricsHistory::new();
 
-        Ok(Self { terminal, history })
+        Ok(Self {
+            terminal,
+            history,
+            latest: None,
+            processes: ProcessTable::new(),
+            pending: None,
+            notice: None,
+            grouped: false,
+            detail: false,
+            detail_cache: None,
+            paused: false,
+            active: false,
+        })
     }
 
     pub fn init(&mut self) -> io::Result<()> {
+        install_panic_hook();
         enable_raw_mode()?;
+        self.active = true;
         execute!(
             io::stdout(),
             EnterAlternateScreen,
@@ -39,94 +88,123 @@ impl Dashboard {
         Ok(())
     }
 
+    // Add a sample to the history and draw it
     pub fn update(&mut self, metrics: &DetailedMetrics) -> io::Result<()> {
-        self.history.add_metrics(metrics);
-        let metrics = metrics.clone();
-        
-        let cpu_data = self.history.get_cpu_data();
-        let mem_data = self.history.get_memory_data();
-        
-        self.terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(1)
-                .constraints([
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(40),
-                    Constraint::Percentage(30),
-                ].as_ref())
-                .split(f.size());
+        self.record(metrics);
+        self.draw(None)
+    }
 
-            let overview_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Percentage(33),
-                    Constraint::Percentage(33),
-                    Constraint::Percentage(34),
-                ].as_ref())
-                .split(chunks[0]);
-
-            let cpu_gauge = Gauge::default()
-                .block(Block::default().title("CPU Usage").borders(Borders::ALL))
-                .gauge_style(Style::default().fg(Color::Cyan))
-                .percent(metrics.basic.cpu_usage as u16);
-            f.render_widget(cpu_gauge, overview_chunks[0]);
-
-            let mem_used_percent = (metrics.basic.used_memory as f64 
-                / metrics.basic.total_memory as f64 * 100.0) as u16;
-            let memory_gauge = Gauge::default()
-                .block(Block::default().title("Memory Usage").borders(Borders::ALL))
-                .gauge_style(Style::default().fg(Color::Magenta))
-                .percent(mem_used_percent);
-            f.render_widget(memory_gauge, overview_chunks[1]);
-
-            let network_info = Paragraph::new(format!(
-                "Network:\nRX: {:.2} MB\nTX: {:.2} MB",
-                metrics.network.rx_bytes as f64 / 1_000_000.0,
-                metrics.network.tx_bytes as f64 / 1_000_000.0,
-            ))
-            .block(Block::default().title("Network").borders(Borders::ALL));
-            f.render_widget(network_info, overview_chunks[2]);
-
-            let chart_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(chunks[1]);
-
-            let cpu_dataset = Dataset::default()
-                .name("CPU Usage")
-                .marker(symbols::Marker::Braille)
-                .style(Style::default().fg(Color::Cyan))
-                .data(&cpu_data);
-
-            let cpu_chart = Chart::new(vec![cpu_dataset])
-                .block(Block::default().title("CPU History").borders(Borders::ALL))
-                .x_axis(Axis::default().bounds([0.0, 100.0]))
-                .y_axis(Axis::default().bounds([0.0, 100.0]));
-            f.render_widget(cpu_chart, chart_chunks[0]);
-
-            let mem_dataset = Dataset::default()
-                .name("Memory Usage")
-                .marker(symbols::Marker::Braille)
-                .style(Style::default().fg(Color::Magenta))
-                .data(&mem_data);
-
-            let mem_chart = Chart::new(vec![mem_dataset])
-                .block(Block::default().title("Memory History").borders(Borders::ALL))
-                .x_axis(Axis::default().bounds([0.0, 100.0]))
-                .y_axis(Axis::default().bounds([0.0, 100.0]));
-            f.render_widget(mem_chart, chart_chunks[1]);
-
-            let processes_block = Block::default()
-                .title("Processes")
-                .borders(Borders::ALL);
-            f.render_widget(processes_block, chunks[2]);
-        })?;
-        
-        Ok(())
+    // Handle input and samples until the user quits. Samples are recorded
+    // as they arrive (unless paused); the screen is redrawn every `refresh`
+    // and straight after a key press or resize.
+    pub fn run(&mut self, sampler: &SamplerHandle, refresh: Duration) -> io::Result<()> {
+        let mut updates = sampler.subscribe();
+        if let Some(metrics) = sampler.latest() {
+            self.record(&metrics);
+        }
+        let mut next_draw = Instant::now();
+
+        loop {
+            loop {
+                match updates.try_recv() {
+                    Ok(metrics) if !self.paused => self.record(&metrics),
+                    Ok(_) | Err(TryRecvError::Lagged(_)) => continue,
+                    Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                }
+            }
+
+            if Instant::now() >= next_draw {
+                self.draw(Some(sampler.interval()))?;
+                next_draw = Instant::now() + refresh;
+            }
+
+            let timeout = next_draw.saturating_duration_since(Instant::now()).min(INPUT_POLL);
+            if !event::poll(timeout)? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) => {
+                    if self.handle_key(&key, sampler) == Some(Action::Quit) {
+                        return Ok(());
+                    }
+                }
+                Event::Resize(..) => {}
+                _ => continue,
+            }
+            next_draw = Instant::now();
+        }
+    }
+
+    // A pending confirmation sees the key first, then the process table
+    // (so typing into its filter works), then the process actions and
+    // the global keys
+    fn handle_key(&mut self, key: &KeyEvent, sampler: &SamplerHandle) -> Option<Action> {
+        // A release must not answer (and cancel) a confirmation
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        self.notice = None;
+        if let Some(pending) = self.pending.take() {
+            self.notice = Some(match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    match perform_audited(pending.pid, Some(&pending.name), pending.action, "tui") {
+                        Ok(()) => format!("{} {} ({}): done", pending.action, pending.pid, pending.name),
+                        Err(e) => format!("{} {} ({}): {}", pending.action, pending.pid, pending.name, e),
+                    }
+                }
+                _ => "Cancelled".to_string(),
+            });
+            return None;
+        }
+        // The process table's keys only apply while it is shown
+        if !self.grouped && self.processes.handle_key(key) {
+            return None;
+        }
+        if let Some(process_key) = process_key_for(key).filter(|_| !self.grouped) {
+            self.request_action(process_key);
+            return None;
+        }
+
+        let action = action_for(key)?;
+        match action {
+            Action::Quit => {}
+            Action::TogglePause => self.paused = !self.paused,
+            Action::ToggleGroups => self.grouped = !self.grouped,
+            Action::ToggleDetail => self.detail = !self.detail,
+            Action::SlowerInterval | Action::FasterInterval => {
+                let slower = action == Action::SlowerInterval;
+                sampler.set_interval(next_interval(sampler.interval(), slower));
+            }
+        }
+        Some(action)
+    }
+
+    fn request_action(&mut self, key: ProcessKey) {
+        let pid = match self.processes.selected_pid() {
+            Some(pid) => pid,
+            None => {
+                self.notice = Some("No process selected".to_string());
+                return;
+            }
+        };
+        let action = match key {
+            ProcessKey::Send(action) => action,
+            ProcessKey::Nice(delta) => match nice(pid) {
+                Ok(current) => ProcessAction::Renice { nice: (current + delta).clamp(NICE_MIN, NICE_MAX) },
+                Err(e) => {
+                    self.notice = Some(format!("Cannot read the nice value of {}: {}", pid, e));
+                    return;
+                }
+            },
+        };
+        let name = self.latest.as_ref()
+            .and_then(|m| m.processes.iter().find(|p| p.pid == pid))
+            .map_or_else(|| "?".to_string(), |p| p.name.clone());
+        self.pending = Some(PendingAction { pid, name, action });
     }
 
     pub fn cleanup(&mut self) -> io::Result<()> {
+        self.active = false;
         disable_raw_mode()?;
         execute!(
             self.terminal.backend_mut(),
@@ -136,4 +214,107 @@ impl Dashboard {
         self.terminal.show_cursor()?;
         Ok(())
     }
-} 
\ No newline at end of file
+
+    fn record(&mut self, metrics: &DetailedMetrics) {
+        self.history.add_metrics(metrics);
+        self.latest = Some(metrics.clone());
+        self.detail_cache = None;
+    }
+
+    // The status bar is only shown when driven by `run`, which knows the
+    // sampling interval
+    fn draw(&mut self, interval: Option<Duration>) -> io::Result<()> {
+        let Self { terminal, history, latest, processes, pending, notice, grouped, detail, detail_cache, paused, .. } = self;
+        let prompt = pending.as_ref().map(|p| format!("{} {} ({})? [y/N]", p.action, p.pid, p.name));
+        // Processes outside the collector's top N are read on demand
+        let selected = latest.as_ref()
+            .filter(|_| *detail && !*grouped)
+            .and_then(|m| m.processes.iter().find(|p| Some(p.pid) == processes.selected_pid()))
+            .map(|p| {
+                let mut process = p.clone();
+                if process.memory_detail.is_none() {
+                    let key = (process.pid, process.start_time);
+                    if detail_cache.as_ref().is_none_or(|(cached, _)| *cached != key) {
+                        *detail_cache = Some((key, read_memory_of(Path::new(PROC_ROOT), key.0, key.1).ok()));
+                    }
+                    process.memory_detail = detail_cache.as_ref().and_then(|(_, memory)| memory.clone());
+                }
+                process
+            });
+
+        terminal.draw(|f| {
+            let outer = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+                .split(f.size());
+            if let Some(interval) = interval {
+                let status = Status {
+                    interval,
+                    paused: *paused,
+                    filtering: processes.is_editing_filter(),
+                    message: prompt.as_deref().or(notice.as_deref()),
+                };
+                components::status_bar(f, outer[1], &status);
+            }
+
+            let metrics = match latest {
+                Some(metrics) => metrics,
+                None => {
+                    components::placeholder(f, outer[0], "Waiting for the first sample...");
+                    return;
+                }
+            };
+
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints([
+                    Constraint::Percentage(20),
+                    Constraint::Length(components::cpu_cores_height(&metrics.cpu, outer[0].width.saturating_sub(2))),
+                    Constraint::Percentage(25),
+                    Constraint::Min(6),
+                ].as_ref())
+                .split(outer[0]);
+
+            components::overview(f, chunks[0], metrics);
+            components::cpu_cores(f, chunks[1], &metrics.cpu);
+            components::history_charts(f, chunks[2], history, metrics);
+            if *grouped {
+                components::cgroup_table(f, chunks[3], &metrics.cgroups);
+            } else if *detail {
+                let split = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                    .split(chunks[3]);
+                processes.render(f, split[0], &metrics.processes, &metrics.leaks);
+                let leak = selected.as_ref().and_then(|p| metrics.leaks.iter().find(|leak| leak.pid == p.pid));
+                components::process_detail(f, split[1], selected.as_ref(), leak);
+            } else {
+                processes.render(f, chunks[3], &metrics.processes, &metrics.leaks);
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = self.cleanup();
+        }
+    }
+}
+
+// Leave raw mode and the alternate screen before the default hook prints
+// the panic, otherwise the message is lost and the shell left unusable
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = disable_raw_mode();
+            let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, Show);
+            default_hook(info);
+        }));
+    });
+}
@@ -13,4 +13,10 @@ pub enum MonitorError {
 
     #[error("Logger error: {0}")]
     Logger(String),
+
+    #[error("HTTP error: {0}")]
+    Http(String),
+
+    #[error("Process action failed: {0}")]
+    ProcessAction(String),
 } 
\ No newline at end of file
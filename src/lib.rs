@@ -1,10 +1,15 @@
+pub mod app;
 pub mod alert;
 pub mod cli;
+pub mod collector;
 pub mod config;
 pub mod display;
 pub mod error;
 pub mod logger;
 pub mod monitor;
+pub mod process;
+pub mod procfs;
+pub mod sampler;
 pub mod storage;
 pub mod utils;
 pub mod web;
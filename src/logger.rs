@@ -1,8 +1,9 @@
 use log::{LevelFilter, error};
 use log4rs::{
     append::rolling_file::RollingFileAppender,
-    append::console::ConsoleAppender,
-    config::{Appender, Config, Root},
+    append::console::{ConsoleAppender, Target},
+    append::file::FileAppender,
+    config::{Appender, Config, Logger, Root},
     encode::pattern::PatternEncoder,
 };
 use log4rs::append::rolling_file::policy::compound::{
@@ -11,39 +12,76 @@ use log4rs::append::rolling_file::policy::compound::{
     roll::fixed_window::FixedWindowRoller,
 };
 use std::{fs, path::Path};
+use crate::config::Config as AppConfig;
 
 const DEFAULT_LOG_PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S.%3f)} [{t}] {l} - {m}\n";
 const CONSOLE_LOG_PATTERN: &str = "{d(%H:%M:%S)} {l} {m}\n";
 const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024; // 10MB
 const LOG_DIR: &str = "logs";
-const LOG_FILE_PATH: &str = "logs/memory_monitor.log";
+const LOG_FILE_NAME: &str = "memory_monitor.log";
+const AUDIT_FILE_NAME: &str = "audit.log";
+const AUDIT_LOG_PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S%.3f %z)} {m}\n";
+// Records of actions taken on the system (signals, renice) go to this
+// target; they land in the audit log as well as the main log
+pub const AUDIT_TARGET: &str = "audit";
 const MAX_BACKUP_FILES: u32 = 5;
 
 pub fn setup_logger() -> Result<(), Box<dyn std::error::Error>> {
-    cleanup_old_logs()?;
-    ensure_log_directory()?;
+    init_logger(LOG_DIR, LevelFilter::Info, MAX_LOG_SIZE, MAX_BACKUP_FILES, true)
+}
+
+// Log to `log_dir` with the configured level and rotation. The console
+// appender writes to stderr so it never mixes with command output; the
+// TUI turns it off since it owns the terminal.
+pub fn setup_logger_from_config(config: &AppConfig, console: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let level: LevelFilter = config.log_level.parse()
+        .map_err(|_| format!("Invalid log level: {}", config.log_level))?;
+    init_logger(&config.log_dir, level, config.max_log_size, config.max_backup_files, console)
+}
+
+fn init_logger(
+    log_dir: &str,
+    level: LevelFilter,
+    max_size: u64,
+    max_backups: u32,
+    console: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    cleanup_old_logs(log_dir)?;
+    ensure_log_directory(log_dir)?;
 
-    let file_appender = create_file_appender()?;
-    let console_appender = create_console_appender();
+    let log_path = Path::new(log_dir).join(LOG_FILE_NAME);
+    let file_appender = create_file_appender(&log_path.to_string_lossy(), max_size, max_backups)?;
 
-    let config = Config::builder()
+    let audit_appender = FileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(AUDIT_LOG_PATTERN)))
+        .append(true)
+        .build(Path::new(log_dir).join(AUDIT_FILE_NAME))?;
+
+    let mut builder = Config::builder()
         .appender(Appender::builder().build("file", Box::new(file_appender)))
-        .appender(Appender::builder().build("console", Box::new(console_appender)))
-        .build(Root::builder()
-            .appender("file")
-            .appender("console")
-            .build(LevelFilter::Info))?;
+        .appender(Appender::builder().build("audit", Box::new(audit_appender)))
+        .logger(Logger::builder().appender("audit").build(AUDIT_TARGET, LevelFilter::Info));
+    let mut root = Root::builder().appender("file");
+    if console {
+        builder = builder.appender(Appender::builder().build("console", Box::new(create_console_appender())));
+        root = root.appender("console");
+    }
+    let config = builder.build(root.build(level))?;
 
     log4rs::init_config(config)?;
     Ok(())
 }
 
-fn create_file_appender() -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
+fn create_file_appender(
+    log_path: &str,
+    max_size: u64,
+    max_backups: u32,
+) -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
     let fixed_window_roller = FixedWindowRoller::builder()
         .base(1)
-        .build(&format!("{}.{{}}.gz", LOG_FILE_PATH), MAX_BACKUP_FILES)?;
+        .build(&format!("{}.{{}}.gz", log_path), max_backups.max(1))?;
     
-    let size_trigger = SizeTrigger::new(MAX_LOG_SIZE);
+    let size_trigger = SizeTrigger::new(max_size);
     let compound_policy = CompoundPolicy::new(
         Box::new(size_trigger),
         Box::new(fixed_window_roller),
@@ -52,25 +90,26 @@ fn create_file_appender() -> Result<RollingFileAppender, Box<dyn std::error::Err
     Ok(RollingFileAppender::builder()
         .encoder(Box::new(PatternEncoder::new(DEFAULT_LOG_PATTERN)))
         .append(true)
-        .build(LOG_FILE_PATH, Box::new(compound_policy))?)
+        .build(log_path, Box::new(compound_policy))?)
 }
 
 fn create_console_appender() -> ConsoleAppender {
     ConsoleAppender::builder()
+        .target(Target::Stderr)
         .encoder(Box::new(PatternEncoder::new(CONSOLE_LOG_PATTERN)))
         .build()
 }
 
-fn ensure_log_directory() -> Result<(), std::io::Error> {
-    if !Path::new(LOG_DIR).exists() {
-        fs::create_dir_all(LOG_DIR)?;
+fn ensure_log_directory(log_dir: &str) -> Result<(), std::io::Error> {
+    if !Path::new(log_dir).exists() {
+        fs::create_dir_all(log_dir)?;
     }
     Ok(())
 }
 
-fn cleanup_old_logs() -> Result<(), std::io::Error> {
-    if Path::new(LOG_DIR).exists() {
-        for entry in fs::read_dir(LOG_DIR)? {
+fn cleanup_old_logs(log_dir: &str) -> Result<(), std::io::Error> {
+    if Path::new(log_dir).exists() {
+        for entry in fs::read_dir(log_dir)? {
             let entry = entry?;
             let path = entry.path();
             if let Some(ext) = path.extension() {
@@ -92,6 +131,8 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    const LOG_FILE_PATH: &str = "logs/memory_monitor.log";
+
     #[test]
     fn test_logger_setup() {
         assert!(setup_logger().is_ok());
@@ -1,6 +1,13 @@
-use actix_web;
+use std::process::ExitCode;
+use clap::Parser;
+use memory_monitor::{app, cli::Args};
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    memory_monitor::web::start_server().await
+fn main() -> ExitCode {
+    match app::run(Args::parse()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(2)
+        }
+    }
 }
@@ -1,11 +1,13 @@
-use sysinfo::{
-    System, SystemExt, ProcessExt, CpuExt, DiskExt, 
-    ComponentExt, NetworkExt, PidExt
-};
+use sysinfo::{System, SystemExt, ProcessExt, CpuExt};
 use log::{warn, error};
+use std::fmt;
 use std::time::SystemTime;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use crate::collector::CollectorRegistry;
 use crate::config::{MEMORY_WARNING_THRESHOLD, MEMORY_CRITICAL_THRESHOLD};
+use crate::procfs::cgroup::CgroupKind;
+use crate::procfs::pressure::Pressure;
+use crate::procfs::stat::CpuBreakdown;
 use serde::Serialize;
 
 // Store the last 100 data points
@@ -15,6 +17,14 @@ const HISTORY_SIZE: usize = 100;
 pub struct MetricsHistory {
     cpu_history: VecDeque<(SystemTime, f32)>,
     memory_history: VecDeque<(SystemTime, f64)>,
+    // Combined rx+tx bytes per second, per interface
+    network_history: BTreeMap<String, VecDeque<u64>>,
+}
+
+impl Default for MetricsHistory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MetricsHistory {
@@ -22,6 +32,7 @@ impl MetricsHistory {
         Self {
             cpu_history: VecDeque::with_capacity(HISTORY_SIZE),
             memory_history: VecDeque::with_capacity(HISTORY_SIZE),
+            network_history: BTreeMap::new(),
         }
     }
 
@@ -41,6 +52,16 @@ impl MetricsHistory {
         if self.memory_history.len() > HISTORY_SIZE {
             self.memory_history.pop_front();
         }
+
+        let interfaces = &metrics.network.interfaces;
+        self.network_history.retain(|name, _| interfaces.iter().any(|i| &i.name == name));
+        for interface in interfaces {
+            let history = self.network_history.entry(interface.name.clone()).or_default();
+            history.push_back((interface.rx_bytes_per_sec + interface.tx_bytes_per_sec) as u64);
+            if history.len() > HISTORY_SIZE {
+                history.pop_front();
+            }
+        }
     }
 
     pub fn get_cpu_data(&self) -> Vec<(f64, f64)> {
@@ -51,6 +72,11 @@ impl MetricsHistory {
             .collect()
     }
 
+    // Oldest first; empty for an interface not seen yet
+    pub fn get_network_data(&self, interface: &str) -> Vec<u64> {
+        self.network_history.get(interface).map(|h| h.iter().copied().collect()).unwrap_or_default()
+    }
+
     pub fn get_memory_data(&self) -> Vec<(f64, f64)> {
         self.memory_history
             .iter()
@@ -99,11 +125,11 @@ pub fn check_memory_usage(usage_percentage: f64) {
 
 pub fn get_top_processes(sys: &System, num_processes: usize) -> Vec<(&sysinfo::Pid, &sysinfo::Process)> {
     let mut processes: Vec<_> = sys.processes().iter().collect();
-    processes.sort_by(|a, b| b.1.memory().cmp(&a.1.memory()));
+    processes.sort_by_key(|(_, process)| std::cmp::Reverse(process.memory()));
     processes.into_iter().take(num_processes).collect()
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SystemMetrics {
     pub cpu_usage: f32,
     pub total_memory: u64,
@@ -112,7 +138,7 @@ pub struct SystemMetrics {
     pub load_average: LoadAvgWrapper,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LoadAvgWrapper {
     pub one: f64,
     pub five: f64,
@@ -157,26 +183,314 @@ pub fn get_system_metrics(sys: &mut System) -> SystemMetrics {
 #[derive(Debug, Clone, Serialize)]
 pub struct DetailedMetrics {
     pub basic: SystemMetrics,
+    pub cpu: CpuMetrics,
+    pub memory: MemoryMetrics,
+    pub pressure: PressureMetrics,
+    // The monitor's own cgroup, on cgroup v2 hosts
+    pub cgroup: Option<CgroupMetrics>,
+    // Every cgroup on the host, largest memory first
+    pub cgroups: Vec<CgroupUsage>,
     pub network: NetworkMetrics,
+    pub sockets: SocketMetrics,
     pub processes: Vec<ProcessMetrics>,
     pub temperatures: Vec<Temperature>,
     pub disks: Vec<DiskMetrics>,
+    // Detected since the previous sample
+    pub events: Vec<MemoryEvent>,
+    // Fastest growing first
+    pub leaks: Vec<LeakSuspect>,
+    // Free-form gauges reported by in-house collectors
+    pub gauges: BTreeMap<String, f64>,
     pub timestamp: SystemTime,
 }
 
+impl Default for DetailedMetrics {
+    fn default() -> Self {
+        Self {
+            basic: SystemMetrics::default(),
+            cpu: CpuMetrics::default(),
+            memory: MemoryMetrics::default(),
+            pressure: PressureMetrics::default(),
+            cgroup: None,
+            cgroups: Vec::new(),
+            network: NetworkMetrics::default(),
+            sockets: SocketMetrics::default(),
+            processes: Vec::new(),
+            temperatures: Vec::new(),
+            disks: Vec::new(),
+            events: Vec::new(),
+            leaks: Vec::new(),
+            gauges: BTreeMap::new(),
+            timestamp: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CpuMetrics {
+    pub cores: Vec<CoreMetrics>,
+    // Time per state since the previous sample, from /proc/stat
+    pub breakdown: CpuBreakdown,
+    // The same per CPU, keyed by name ("cpu0")
+    pub core_breakdowns: BTreeMap<String, CpuBreakdown>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CoreMetrics {
+    pub name: String,
+    pub usage: f32,
+    pub frequency_mhz: u64,
+}
+
+// Breakdown from /proc/meminfo and /proc/vmstat. Sizes are in bytes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryMetrics {
+    pub swap_total: u64,
+    pub swap_used: u64,
+    pub swap_cached: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub shared: u64,
+    pub slab: u64,
+    pub slab_reclaimable: u64,
+    pub dirty: u64,
+    pub writeback: u64,
+    pub committed_as: u64,
+    pub commit_limit: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub hugepage_size: u64,
+    // Cumulative counters, in pages for swap
+    pub swap_in_pages: u64,
+    pub swap_out_pages: u64,
+    pub major_faults: u64,
+    pub oom_kills: u64,
+    pub swap_in_pages_per_sec: f64,
+    pub swap_out_pages_per_sec: f64,
+    pub page_faults_per_sec: f64,
+    pub major_faults_per_sec: f64,
+}
+
+// Pressure stall information from /proc/pressure. None where the kernel
+// does not provide it, so alert rules on it never fire there.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PressureMetrics {
+    pub cpu: Option<Pressure>,
+    pub memory: Option<Pressure>,
+    pub io: Option<Pressure>,
+}
+
+impl PressureMetrics {
+    pub fn is_available(&self) -> bool {
+        self.cpu.is_some() || self.memory.is_some() || self.io.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CgroupMetrics {
+    pub path: String,
+    // Whether `basic` memory and CPU figures are relative to these limits
+    pub limits_applied: bool,
+    pub memory_current: u64,
+    pub memory_working_set: u64,
+    // None when unlimited
+    pub memory_max: Option<u64>,
+    pub memory_high: Option<u64>,
+    // Working set against memory.max
+    pub memory_usage_percent: Option<f64>,
+    // Cumulative memory.events counters
+    pub memory_max_events: u64,
+    pub oom_events: u64,
+    pub oom_kill_events: u64,
+    // cpu.max quota in CPUs, e.g. 1.5
+    pub cpu_quota_cores: Option<f64>,
+    // Usage over the last interval against the quota
+    pub cpu_usage_percent: Option<f64>,
+    pub cpu_usage_usec: u64,
+    pub cpu_nr_periods: u64,
+    pub cpu_nr_throttled: u64,
+    pub cpu_throttled_usec: u64,
+    // Share of enforcement periods in the last interval that were throttled
+    pub cpu_throttled_percent: f64,
+}
+
 #[derive(Debug, Clone, Serialize)]
+pub struct CgroupUsage {
+    pub path: String,
+    pub kind: CgroupKind,
+    // Container id, pod uid or unit name
+    pub name: String,
+    // Includes descendant cgroups
+    pub memory_current: u64,
+    pub memory_max: Option<u64>,
+    // Percent of one CPU over the last interval, like process CPU usage
+    pub cpu_usage: f64,
+    pub io_read_bytes: u64,
+    pub io_write_bytes: u64,
+    pub io_read_bytes_per_sec: f64,
+    pub io_write_bytes_per_sec: f64,
+    // Processes directly in this cgroup
+    pub pids: Vec<u32>,
+    // Cumulative memory.events counters, including descendant cgroups
+    pub memory_high_events: u64,
+    pub memory_max_events: u64,
+    pub oom_kill_events: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryEventKind {
+    // A process was killed by the OOM killer
+    OomKill,
+    // A cgroup hit memory.max and had to reclaim or fail allocations
+    MemoryMax,
+    // A cgroup went over memory.high and was throttled
+    MemoryHigh,
+    // A process started to look like it is leaking memory
+    LeakSuspected,
+}
+
+impl fmt::Display for MemoryEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MemoryEventKind::OomKill => "oom_kill",
+            MemoryEventKind::MemoryMax => "memory_max",
+            MemoryEventKind::MemoryHigh => "memory_high",
+            MemoryEventKind::LeakSuspected => "leak_suspected",
+        })
+    }
+}
+
+// Something that happened since the previous sample, as opposed to a
+// level that was measured
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryEvent {
+    pub kind: MemoryEventKind,
+    pub timestamp: SystemTime,
+    // Cgroup the event was counted in, None for host-wide OOM kills
+    pub cgroup: Option<String>,
+    // The process that was killed, when it could be told from the ones
+    // that disappeared since the previous sample
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    // Its last seen memory usage
+    pub memory: Option<u64>,
+    // How many times it happened since the previous event of this kind
+    pub count: u64,
+    pub message: String,
+}
+
+// Totals cover the interfaces that pass the configured filter
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct NetworkMetrics {
     pub rx_bytes: u64,
     pub tx_bytes: u64,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    // Established TCP connections, from the sockets collector
     pub connections: usize,
+    pub interfaces: Vec<InterfaceMetrics>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InterfaceMetrics {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_packets_per_sec: f64,
+    pub tx_packets_per_sec: f64,
+}
+
+// TCP and UDP sockets from /proc/net, IPv4 and IPv6 together
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SocketMetrics {
+    // Keyed by state name ("ESTABLISHED", "TIME_WAIT", ...)
+    pub tcp_states: BTreeMap<String, usize>,
+    pub udp_sockets: usize,
+    pub listening: Vec<ListeningSocket>,
+    // Remote addresses with the most established TCP connections
+    pub top_peers: Vec<Peer>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ListeningSocket {
+    pub protocol: String,
+    pub address: String,
+    pub port: u16,
+    // Unknown when the socket belongs to another user and we are not root
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Peer {
+    pub address: String,
+    pub connections: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ProcessMetrics {
     pub pid: u32,
     pub name: String,
     pub cpu_usage: f32,
     pub memory: u64,
+    // As reported by the OS, e.g. "Runnable" or "Sleeping"
+    pub status: String,
+    pub parent_pid: Option<u32>,
+    // User name, or the numeric uid when it cannot be resolved
+    pub user: Option<String>,
+    // Full command line; empty for kernel threads
+    pub command: String,
+    // Seconds since the Unix epoch
+    pub start_time: u64,
+    // cgroup v2 path, e.g. "/system.slice/nginx.service"
+    pub cgroup: Option<String>,
+    // Only for the largest processes, see `ProcessMemory`
+    pub memory_detail: Option<ProcessMemory>,
+}
+
+// A process whose RSS has been growing steadily, see `LeakConfig`
+#[derive(Debug, Clone, Serialize)]
+pub struct LeakSuspect {
+    pub pid: u32,
+    pub name: String,
+    pub start_time: u64,
+    pub memory: u64,
+    // Slope of the fitted line
+    pub growth_bytes_per_hour: f64,
+    pub r_squared: f64,
+    pub monotonic_ratio: f64,
+    // Growth between the first and last point, and the time they span
+    pub growth_bytes: i64,
+    pub observed_secs: u64,
+    pub samples: usize,
+}
+
+// Where a process's memory goes, in bytes. `rss` counts shared pages in
+// full for every process mapping them; `pss` splits them between those
+// processes and `uss` leaves them out. The smaps_rollup figures are None
+// for processes we may not inspect (other users' unless root).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProcessMemory {
+    pub rss: u64,
+    pub pss: Option<u64>,
+    pub uss: Option<u64>,
+    pub shared: Option<u64>,
+    pub swap: u64,
+    pub swap_pss: Option<u64>,
+    pub anonymous: u64,
+    pub file_backed: u64,
+    pub shmem: u64,
+    pub peak_rss: u64,
+    pub threads: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -188,109 +502,26 @@ pub struct Temperature {
 #[derive(Debug, Clone, Serialize)]
 pub struct DiskMetrics {
     pub name: String,
+    pub mount_point: String,
+    pub file_system: String,
     pub total_space: u64,
     pub available_space: u64,
     pub read_bytes: u64,
     pub write_bytes: u64,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
 }
 
+// One-shot collection using the built-in collectors. CPU usage needs two
+// refreshes some time apart, so this primes the collectors and samples
+// again. Long-running callers should keep a `CollectorRegistry` instead.
 pub fn get_detailed_metrics(sys: &mut System) -> DetailedMetrics {
-    // First refresh all data
-    sys.refresh_all();
-    
-    // Then collect all metrics
-    let basic = get_system_metrics(sys);
-    let network = get_network_metrics(sys);
-    let processes = get_process_metrics(sys);
-    let temperatures = get_temperature_metrics(sys);
-    let disks = get_disk_metrics(sys);
-
-    DetailedMetrics {
-        basic,
-        network,
-        processes,
-        temperatures,
-        disks,
-        timestamp: SystemTime::now(),
-    }
-}
-
-fn get_disk_metrics(sys: &mut System) -> Vec<DiskMetrics> {
-    sys.refresh_disks();  // Refresh disk data
-    sys.disks().iter().map(|disk| {
-        #[cfg(target_os = "linux")]
-        let (read_bytes, write_bytes) = {
-            use std::fs::File;
-            use std::io::Read;
-            let mut buffer = String::new();
-            if let Ok(mut file) = File::open("/proc/diskstats") {
-                if file.read_to_string(&mut buffer).is_ok() {
-                    // Parse disk stats from /proc/diskstats
-                    // TODO: Implement parsing logic
-                    (0, 0)
-                } else {
-                    (0, 0)
-                }
-            } else {
-                (0, 0)
-            }
-        };
-
-        #[cfg(not(target_os = "linux"))]
-        let (read_bytes, write_bytes) = (0, 0);
-
-        DiskMetrics {
-            name: disk.name().to_string_lossy().into_owned(),
-            total_space: disk.total_space(),
-            available_space: disk.available_space(),
-            read_bytes,
-            write_bytes,
-        }
-    }).collect()
-}
-
-fn get_process_metrics(sys: &mut System) -> Vec<ProcessMetrics> {
-    sys.refresh_processes();
-
-    sys.processes()
-        .iter()
-        .map(|(_, process)| ProcessMetrics {
-            pid: process.pid().as_u32(),
-            name: process.name().to_string(),
-            cpu_usage: process.cpu_usage(),
-            memory: process.memory(),
-        })
-        .collect()
+    let mut registry = CollectorRegistry::with_defaults();
+    registry.collect(sys);
+    std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+    registry.collect(sys)
 }
 
 fn calculate_memory_percentage(used: u64, total: u64) -> f64 {
     (used as f64 / total as f64) * 100.0
 }
-
-fn get_network_metrics(sys: &mut System) -> NetworkMetrics {
-    sys.refresh_networks();
-    
-    let mut rx_bytes = 0;
-    let mut tx_bytes = 0;
-    let mut connections = 0;
-
-    for (_name, data) in sys.networks() {
-        rx_bytes += data.total_received();
-        tx_bytes += data.total_transmitted();
-        connections += 1;
-    }
-
-    NetworkMetrics {
-        rx_bytes,
-        tx_bytes,
-        connections,
-    }
-}
-
-fn get_temperature_metrics(sys: &mut System) -> Vec<Temperature> {
-    sys.components().iter()
-        .map(|component| Temperature {
-            label: component.label().to_string(),
-            value: component.temperature(),
-        }).collect()
-} 
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::io;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use crate::error::MonitorError;
+use crate::logger::AUDIT_TARGET;
+
+// Where the C library keeps errno; the name differs between platforms
+#[cfg(any(target_os = "linux", target_os = "emscripten", target_os = "redox", target_os = "dragonfly"))]
+use libc::__errno_location as errno_location;
+#[cfg(any(target_os = "android", target_os = "openbsd", target_os = "netbsd"))]
+use libc::__errno as errno_location;
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+use libc::__error as errno_location;
+
+// Nice values accepted by setpriority(2)
+pub const NICE_MIN: i32 = -20;
+pub const NICE_MAX: i32 = 19;
+
+// Things that can be done to a process. In JSON: `{"action": "term"}` or
+// `{"action": "renice", "nice": 10}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ProcessAction {
+    Term,
+    Kill,
+    Stop,
+    Cont,
+    Renice { nice: i32 },
+}
+
+impl fmt::Display for ProcessAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessAction::Term => f.write_str("SIGTERM"),
+            ProcessAction::Kill => f.write_str("SIGKILL"),
+            ProcessAction::Stop => f.write_str("SIGSTOP"),
+            ProcessAction::Cont => f.write_str("SIGCONT"),
+            ProcessAction::Renice { nice } => write!(f, "renice to {}", nice),
+        }
+    }
+}
+
+// Send the signal or change the nice value. The OS decides what is
+// permitted; pid 0 (the process group) and the monitor itself are refused.
+pub fn perform(pid: u32, action: ProcessAction) -> Result<(), MonitorError> {
+    let target = match libc::pid_t::try_from(pid) {
+        Ok(target) if target > 0 => target,
+        _ => return Err(MonitorError::ProcessAction(format!("Invalid pid {}", pid))),
+    };
+    if pid == std::process::id() {
+        return Err(MonitorError::ProcessAction("Refusing to act on the monitor itself".to_string()));
+    }
+
+    let signal = match action {
+        ProcessAction::Term => libc::SIGTERM,
+        ProcessAction::Kill => libc::SIGKILL,
+        ProcessAction::Stop => libc::SIGSTOP,
+        ProcessAction::Cont => libc::SIGCONT,
+        ProcessAction::Renice { nice } => return renice(target, nice),
+    };
+    // SAFETY: kill has no memory-safety preconditions
+    check(unsafe { libc::kill(target, signal) })
+}
+
+fn renice(target: libc::pid_t, nice: i32) -> Result<(), MonitorError> {
+    if !(NICE_MIN..=NICE_MAX).contains(&nice) {
+        return Err(MonitorError::ProcessAction(format!(
+            "Nice value {} is outside {}..={}", nice, NICE_MIN, NICE_MAX
+        )));
+    }
+    // SAFETY: setpriority has no memory-safety preconditions
+    check(unsafe { libc::setpriority(libc::PRIO_PROCESS, target as libc::id_t, nice) })
+}
+
+fn check(result: libc::c_int) -> Result<(), MonitorError> {
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error().into())
+    }
+}
+
+// The process's current nice value
+pub fn nice(pid: u32) -> Result<i32, MonitorError> {
+    // -1 is a valid priority, so errors can only be told apart via errno
+    // SAFETY: errno is thread-local and getpriority has no preconditions
+    unsafe {
+        *errno_location() = 0;
+        let nice = libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t);
+        match io::Error::last_os_error() {
+            e if e.raw_os_error() == Some(0) => Ok(nice),
+            e => Err(e.into()),
+        }
+    }
+}
+
+// `perform`, recorded in the audit log whatever the outcome. `origin`
+// says who asked, e.g. "tui" or "api 10.0.0.5".
+pub fn perform_audited(pid: u32, name: Option<&str>, action: ProcessAction, origin: &str) -> Result<(), MonitorError> {
+    let result = perform(pid, action);
+    let name = name.unwrap_or("?");
+    match &result {
+        Ok(()) => info!(target: AUDIT_TARGET, "{} {} pid {} ({}): ok", origin, action, pid, name),
+        Err(e) => warn!(target: AUDIT_TARGET, "{} {} pid {} ({}): failed: {}", origin, action, pid, name, e),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_action_json_format() {
+        let renice: ProcessAction = serde_json::from_str(r#"{"action": "renice", "nice": 5}"#).unwrap();
+        assert_eq!(renice, ProcessAction::Renice { nice: 5 });
+        assert_eq!(serde_json::to_string(&ProcessAction::Kill).unwrap(), r#"{"action":"kill"}"#);
+        assert!(serde_json::from_str::<ProcessAction>(r#"{"action": "hup"}"#).is_err());
+    }
+
+    #[test]
+    fn test_signals_and_renice_on_a_child() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+
+        let before = nice(pid).unwrap();
+        let lower = (before + 1).min(NICE_MAX);
+        perform(pid, ProcessAction::Renice { nice: lower }).unwrap();
+        assert_eq!(nice(pid).unwrap(), lower);
+        assert!(perform(pid, ProcessAction::Renice { nice: 40 }).is_err());
+
+        perform(pid, ProcessAction::Stop).unwrap();
+        perform(pid, ProcessAction::Cont).unwrap();
+        perform(pid, ProcessAction::Term).unwrap();
+        assert!(!child.wait().unwrap().success());
+
+        assert!(perform(0, ProcessAction::Kill).is_err());
+        assert!(perform(std::process::id(), ProcessAction::Stop).is_err());
+    }
+}
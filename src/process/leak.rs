@@ -0,0 +1,168 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use crate::config::LeakConfig;
+use crate::monitor::{LeakSuspect, ProcessMetrics};
+
+// A least-squares line through a process's memory over time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trend {
+    pub bytes_per_sec: f64,
+    pub r_squared: f64,
+    // Share of steps between consecutive points that did not shrink
+    pub monotonic_ratio: f64,
+}
+
+// `points` are (seconds, bytes). None for fewer than two points or when
+// they were all taken at the same time.
+pub fn trend(points: &[(f64, f64)]) -> Option<Trend> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for &(x, y) in points {
+        sxx += (x - mean_x) * (x - mean_x);
+        sxy += (x - mean_x) * (y - mean_y);
+        syy += (y - mean_y) * (y - mean_y);
+    }
+    if sxx == 0.0 {
+        return None;
+    }
+    // A flat line explains nothing
+    let r_squared = if syy == 0.0 { 0.0 } else { sxy * sxy / (sxx * syy) };
+    let steps = points.windows(2).filter(|w| w[1].1 >= w[0].1).count();
+    Some(Trend {
+        bytes_per_sec: sxy / sxx,
+        r_squared,
+        monotonic_ratio: steps as f64 / (points.len() - 1) as f64,
+    })
+}
+
+// Memory history of every process, keyed by pid and start time so a
+// reused pid starts over
+pub struct LeakDetector {
+    config: LeakConfig,
+    histories: HashMap<(u32, u64), VecDeque<(Instant, u64)>>,
+}
+
+impl LeakDetector {
+    pub fn new(config: LeakConfig) -> Self {
+        Self { config, histories: HashMap::new() }
+    }
+
+    // Record the processes' memory, at most one point per sample interval,
+    // and return the suspected leaks, fastest growing first
+    pub fn observe(&mut self, processes: &[ProcessMetrics], now: Instant) -> Vec<LeakSuspect> {
+        let window = Duration::from_secs(self.config.window_secs);
+        let interval = Duration::from_secs(self.config.sample_interval_secs);
+        let mut live = HashMap::with_capacity(processes.len());
+        let mut suspects = Vec::new();
+
+        for process in processes {
+            let key = (process.pid, process.start_time);
+            let mut history = self.histories.remove(&key).unwrap_or_default();
+            if history.back().is_none_or(|&(at, _)| now.saturating_duration_since(at) >= interval) {
+                history.push_back((now, process.memory));
+            }
+            while history.front().is_some_and(|&(at, _)| now.saturating_duration_since(at) > window) {
+                history.pop_front();
+            }
+            if let Some(suspect) = self.judge(process, &history) {
+                suspects.push(suspect);
+            }
+            live.insert(key, history);
+        }
+        // Exited processes are dropped
+        self.histories = live;
+
+        suspects.sort_by(|a, b| b.growth_bytes_per_hour.total_cmp(&a.growth_bytes_per_hour));
+        suspects
+    }
+
+    fn judge(&self, process: &ProcessMetrics, history: &VecDeque<(Instant, u64)>) -> Option<LeakSuspect> {
+        if history.len() < self.config.min_samples.max(2) {
+            return None;
+        }
+        let (start, first) = *history.front()?;
+        let (end, last) = *history.back()?;
+        let points: Vec<(f64, f64)> = history.iter()
+            .map(|&(at, memory)| (at.saturating_duration_since(start).as_secs_f64(), memory as f64))
+            .collect();
+        let trend = trend(&points)?;
+        let per_hour = trend.bytes_per_sec * 3600.0;
+        let growing = last > first
+            && per_hour >= self.config.min_growth_bytes_per_hour as f64
+            && trend.r_squared >= self.config.min_r_squared
+            && trend.monotonic_ratio >= self.config.min_monotonic_ratio;
+        if !growing {
+            return None;
+        }
+        Some(LeakSuspect {
+            pid: process.pid,
+            name: process.name.clone(),
+            start_time: process.start_time,
+            memory: process.memory,
+            growth_bytes_per_hour: per_hour,
+            r_squared: trend.r_squared,
+            monotonic_ratio: trend.monotonic_ratio,
+            growth_bytes: last as i64 - first as i64,
+            observed_secs: end.saturating_duration_since(start).as_secs(),
+            samples: history.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trend() {
+        let line: Vec<_> = (0..10).map(|i| (i as f64, 100.0 + 5.0 * i as f64)).collect();
+        let fit = trend(&line).unwrap();
+        assert!((fit.bytes_per_sec - 5.0).abs() < 1e-9);
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+        assert_eq!(fit.monotonic_ratio, 1.0);
+
+        let flat = trend(&[(0.0, 7.0), (1.0, 7.0), (2.0, 7.0)]).unwrap();
+        assert_eq!((flat.bytes_per_sec, flat.r_squared), (0.0, 0.0));
+        assert_eq!(trend(&[(0.0, 1.0)]), None);
+        assert_eq!(trend(&[(3.0, 1.0), (3.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn test_detects_steady_growth_only() {
+        const MB: u64 = 1024 * 1024;
+        let config = LeakConfig { min_samples: 10, sample_interval_secs: 60, ..LeakConfig::default() };
+        let mut detector = LeakDetector::new(config);
+        let start = Instant::now();
+        let process = |pid: u32, memory: u64| ProcessMetrics { pid, name: format!("p{}", pid), memory, ..ProcessMetrics::default() };
+
+        let mut suspects = Vec::new();
+        for minute in 0..30u64 {
+            let processes = vec![
+                // 1 MB a minute, with the odd dip from freeing
+                process(1, 100 * MB + minute * MB - if minute % 10 == 5 { 2 * MB } else { 0 }),
+                // Large but stable, wobbling around 500 MB
+                process(2, 500 * MB + (minute % 3) * MB),
+                // Grows slowly: 2 MB an hour
+                process(3, 50 * MB + minute * MB / 30),
+            ];
+            suspects = detector.observe(&processes, start + Duration::from_secs(minute * 60));
+            // Samples inside the interval are not recorded
+            detector.observe(&processes, start + Duration::from_secs(minute * 60 + 10));
+        }
+        assert_eq!(suspects.len(), 1);
+        let leak = &suspects[0];
+        assert_eq!((leak.pid, leak.samples, leak.observed_secs), (1, 30, 29 * 60));
+        assert!((leak.growth_bytes_per_hour / MB as f64 - 60.0).abs() < 2.0);
+        assert!(leak.monotonic_ratio < 1.0);
+
+        // A new process under the same pid starts from scratch
+        let restarted = ProcessMetrics { start_time: 1, ..process(1, 10 * MB) };
+        assert!(detector.observe(&[restarted], start + Duration::from_secs(31 * 60)).is_empty());
+        assert_eq!(detector.histories.len(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::monitor::ProcessMemory;
+use crate::procfs::smaps::SmapsRollup;
+use crate::procfs::status::ProcStatus;
+
+pub const PROC_ROOT: &str = "/proc";
+
+// Fails only if the process is gone; smaps_rollup is optional
+pub fn read_memory(proc_root: &Path, pid: u32) -> io::Result<ProcessMemory> {
+    let status = ProcStatus::read(proc_root, pid)?;
+    let rollup = SmapsRollup::read(proc_root, pid).ok();
+    Ok(ProcessMemory {
+        rss: status.vm_rss,
+        pss: rollup.map(|r| r.pss),
+        uss: rollup.map(|r| r.uss()),
+        shared: rollup.map(|r| r.shared()),
+        swap: status.vm_swap,
+        swap_pss: rollup.map(|r| r.swap_pss),
+        anonymous: status.rss_anon,
+        file_backed: status.rss_file,
+        shmem: status.rss_shmem,
+        peak_rss: status.vm_hwm,
+        threads: status.threads,
+    })
+}
+
+// `read_memory`, but only while `pid` is still the process that started
+// at `start_time`: a sample can be older than the reuse of its pids
+pub fn read_memory_of(proc_root: &Path, pid: u32, start_time: u64) -> io::Result<ProcessMemory> {
+    let memory = read_memory(proc_root, pid)?;
+    // Checked afterwards, so a pid reused while reading is caught too.
+    // sysinfo truncates to whole seconds as well, allow for rounding.
+    if read_start_time(proc_root, pid)?.abs_diff(start_time) > 1 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("process {} has exited", pid)));
+    }
+    Ok(memory)
+}
+
+// Seconds since the epoch, like sysinfo's start_time: the boot time plus
+// field 22 of /proc/<pid>/stat, in clock ticks since boot
+pub fn read_start_time(proc_root: &Path, pid: u32) -> io::Result<u64> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("malformed stat for process {}", pid));
+    let stat = fs::read_to_string(proc_root.join(pid.to_string()).join("stat"))?;
+    // The command name may contain spaces and parentheses; fields after
+    // it start with the third, the state
+    let ticks: u64 = stat.rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_whitespace().nth(19))
+        .and_then(|ticks| ticks.parse().ok())
+        .ok_or_else(invalid)?;
+    let boot_time: u64 = fs::read_to_string(proc_root.join("stat"))?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|secs| secs.trim().parse().ok())
+        .ok_or_else(invalid)?;
+    Ok(boot_time + ticks / clock_ticks())
+}
+
+fn clock_ticks() -> u64 {
+    // SAFETY: sysconf has no preconditions
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_time_guards_reused_pids() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("42");
+        fs::create_dir(&dir).unwrap();
+        fs::write(root.path().join("stat"), "cpu  1 2 3 4\nbtime 1700000000\n").unwrap();
+        fs::write(dir.join("status"), "Name:\tweb (1)\nVmRSS:\t    2048 kB\nThreads:\t4\n").unwrap();
+        let started = 1_700_000_000 + 500;
+        let ticks = 500 * clock_ticks();
+        fs::write(dir.join("stat"), format!("42 (web (1) x) S 1 42 42 0 -1 4194560 0 0 0 0 0 0 0 0 20 0 4 0 {} 0 0\n", ticks)).unwrap();
+
+        assert_eq!(read_start_time(root.path(), 42).unwrap(), started);
+        assert_eq!(read_memory_of(root.path(), 42, started).unwrap().rss, 2048 * 1024);
+        let reused = read_memory_of(root.path(), 42, started - 60).unwrap_err();
+        assert_eq!(reused.kind(), io::ErrorKind::NotFound);
+    }
+}
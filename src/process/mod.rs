@@ -0,0 +1,9 @@
+mod actions;
+mod leak;
+mod memory;
+mod tree;
+
+pub use self::actions::{nice, perform, perform_audited, ProcessAction, NICE_MAX, NICE_MIN};
+pub use self::leak::{trend, LeakDetector, Trend};
+pub use self::memory::{read_memory, read_memory_of, read_start_time, PROC_ROOT};
+pub use self::tree::{build_tree, filter_tree, flatten, sort_tree, ProcessNode, TreeRow};
@@ -0,0 +1,149 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use crate::monitor::ProcessMetrics;
+
+// A process with its children and the totals of its whole subtree
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessNode {
+    #[serde(flatten)]
+    pub process: ProcessMetrics,
+    // This process plus every descendant
+    pub total_cpu_usage: f32,
+    pub total_memory: u64,
+    pub descendants: usize,
+    pub children: Vec<ProcessNode>,
+}
+
+// Roots are processes whose parent is unknown or not in the list (pid 1,
+// kthreadd, or children of processes that exited between refreshes).
+// Children are ordered by pid.
+pub fn build_tree(processes: &[ProcessMetrics]) -> Vec<ProcessNode> {
+    let pids: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+    let mut children: HashMap<u32, Vec<&ProcessMetrics>> = HashMap::new();
+    let mut roots = Vec::new();
+    for process in processes {
+        match process.parent_pid {
+            Some(parent) if parent != process.pid && pids.contains(&parent) => {
+                children.entry(parent).or_default().push(process)
+            }
+            _ => roots.push(process),
+        }
+    }
+
+    let mut nodes: Vec<_> = roots.into_iter().map(|root| build_node(root, &children)).collect();
+    sort_tree(&mut nodes, |a, b| a.process.pid.cmp(&b.process.pid));
+    nodes
+}
+
+fn build_node(process: &ProcessMetrics, children: &HashMap<u32, Vec<&ProcessMetrics>>) -> ProcessNode {
+    let children: Vec<_> = children
+        .get(&process.pid)
+        .map(|list| list.iter().map(|child| build_node(child, children)).collect())
+        .unwrap_or_default();
+    ProcessNode {
+        total_cpu_usage: process.cpu_usage + children.iter().map(|c| c.total_cpu_usage).sum::<f32>(),
+        total_memory: process.memory + children.iter().map(|c| c.total_memory).sum::<u64>(),
+        descendants: children.iter().map(|c| 1 + c.descendants).sum(),
+        process: process.clone(),
+        children,
+    }
+}
+
+// Order siblings at every level
+pub fn sort_tree<F>(nodes: &mut [ProcessNode], compare: F)
+where
+    F: Fn(&ProcessNode, &ProcessNode) -> Ordering + Copy,
+{
+    nodes.sort_by(compare);
+    for node in nodes {
+        sort_tree(&mut node.children, compare);
+    }
+}
+
+// Keep the processes that match and their ancestors, so matches are still
+// shown in context. Totals keep describing the full subtree.
+pub fn filter_tree<F>(nodes: Vec<ProcessNode>, keep: &F) -> Vec<ProcessNode>
+where
+    F: Fn(&ProcessMetrics) -> bool,
+{
+    nodes
+        .into_iter()
+        .filter_map(|mut node| {
+            node.children = filter_tree(std::mem::take(&mut node.children), keep);
+            (keep(&node.process) || !node.children.is_empty()).then_some(node)
+        })
+        .collect()
+}
+
+pub struct TreeRow<'a> {
+    pub depth: usize,
+    // Box-drawing guides, e.g. "│  └─ "
+    pub prefix: String,
+    pub node: &'a ProcessNode,
+}
+
+// Depth-first rows for display
+pub fn flatten(nodes: &[ProcessNode]) -> Vec<TreeRow<'_>> {
+    let mut rows = Vec::new();
+    flatten_into(nodes, &mut String::new(), 0, &mut rows);
+    rows
+}
+
+fn flatten_into<'a>(nodes: &'a [ProcessNode], guides: &mut String, depth: usize, rows: &mut Vec<TreeRow<'a>>) {
+    for (i, node) in nodes.iter().enumerate() {
+        let last = i + 1 == nodes.len();
+        let prefix = match depth {
+            0 => String::new(),
+            _ => format!("{}{}", guides, if last { "└─ " } else { "├─ " }),
+        };
+        rows.push(TreeRow { depth, prefix, node });
+
+        let len = guides.len();
+        if depth > 0 {
+            guides.push_str(if last { "   " } else { "│  " });
+        }
+        flatten_into(&node.children, guides, depth + 1, rows);
+        guides.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, parent_pid: Option<u32>, name: &str, memory: u64) -> ProcessMetrics {
+        ProcessMetrics { pid, parent_pid, name: name.to_string(), memory, cpu_usage: 1.0, ..ProcessMetrics::default() }
+    }
+
+    #[test]
+    fn test_tree_totals_and_rows() {
+        let processes = vec![
+            process(1, None, "init", 10),
+            process(20, Some(1), "firefox", 100),
+            process(21, Some(20), "web content", 300),
+            process(22, Some(20), "gpu", 50),
+            process(30, Some(1), "sshd", 5),
+            // Its parent already exited
+            process(40, Some(99), "orphan", 1),
+        ];
+        let tree = build_tree(&processes);
+        assert_eq!(tree.iter().map(|n| n.process.pid).collect::<Vec<_>>(), vec![1, 40]);
+        let firefox = &tree[0].children[0];
+        assert_eq!(firefox.total_memory, 450);
+        assert_eq!(firefox.total_cpu_usage, 3.0);
+        assert_eq!(firefox.descendants, 2);
+        assert_eq!(tree[0].descendants, 4);
+
+        let rows: Vec<_> = flatten(&tree).iter().map(|r| format!("{}{}", r.prefix, r.node.process.name)).collect();
+        assert_eq!(rows, vec!["init", "├─ firefox", "│  ├─ web content", "│  └─ gpu", "└─ sshd", "orphan"]);
+
+        let mut sorted = tree.clone();
+        sort_tree(&mut sorted, |a, b| b.total_memory.cmp(&a.total_memory));
+        assert_eq!(sorted[0].children[0].children[0].process.name, "web content");
+
+        let filtered = filter_tree(tree, &|p: &ProcessMetrics| p.name == "gpu");
+        let rows: Vec<_> = flatten(&filtered).iter().map(|r| r.node.process.pid).collect();
+        assert_eq!(rows, vec![1, 20, 22]);
+    }
+}
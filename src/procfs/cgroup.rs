@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+pub const SELF_CGROUP_PATH: &str = "/proc/self/cgroup";
+
+// A cgroup v2 directory and its path relative to the mount point
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cgroup {
+    pub path: String,
+    pub dir: PathBuf,
+}
+
+impl Cgroup {
+    // Inside a cgroup namespace (containers), our cgroup is the root of
+    // what we can see and /proc/self/cgroup says "/"
+    pub fn is_namespace_root(&self) -> bool {
+        self.path == "/"
+    }
+
+    fn read(&self, file: &str) -> io::Result<String> {
+        fs::read_to_string(self.dir.join(file))
+    }
+}
+
+// The cgroup v2 entry ("0::/some/path") of a /proc/<pid>/cgroup file.
+// Hybrid hosts list v1 controllers as well; those lines are ignored.
+pub fn parse_membership(content: &str) -> Option<&str> {
+    content.lines().find_map(|line| line.strip_prefix("0::")).map(str::trim)
+}
+
+// The cgroup of the process whose /proc/<pid>/cgroup is `self_cgroup`,
+// with cgroup2 mounted at `root`. None on cgroup v1 or hybrid hosts,
+// where the unified hierarchy has no controllers.
+pub fn detect(root: &Path, self_cgroup: &str) -> Option<Cgroup> {
+    if !root.join("cgroup.controllers").is_file() {
+        return None;
+    }
+    let path = parse_membership(self_cgroup)?;
+    let dir = root.join(path.trim_start_matches('/'));
+    // Without a cgroup namespace a container may still see the host's
+    // path while its own cgroup is mounted at the root
+    let dir = if dir.is_dir() { dir } else { root.to_path_buf() };
+    Some(Cgroup { path: path.to_string(), dir })
+}
+
+// Counters from memory.events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryEvents {
+    pub low: u64,
+    pub high: u64,
+    pub max: u64,
+    pub oom: u64,
+    pub oom_kill: u64,
+}
+
+// Counters from cpu.stat, in microseconds where named so
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuStat {
+    pub usage_usec: u64,
+    pub user_usec: u64,
+    pub system_usec: u64,
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled_usec: u64,
+}
+
+// cpu.max: quota and period in microseconds; no quota for "max"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuMax {
+    pub quota: Option<u64>,
+    pub period: u64,
+}
+
+impl CpuMax {
+    // The quota as a number of CPUs, e.g. 1.5
+    pub fn cores(&self) -> Option<f64> {
+        let quota = self.quota?;
+        (self.period > 0).then(|| quota as f64 / self.period as f64)
+    }
+}
+
+// Everything read from one cgroup. Files belonging to controllers that
+// are not enabled for the cgroup read as defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CgroupStats {
+    pub memory_current: u64,
+    // None for "max" (no limit)
+    pub memory_max: Option<u64>,
+    pub memory_high: Option<u64>,
+    // memory.stat inactive_file: page cache the kernel can drop first
+    pub inactive_file: u64,
+    pub memory_events: MemoryEvents,
+    pub cpu_max: CpuMax,
+    pub cpu_stat: CpuStat,
+}
+
+impl CgroupStats {
+    // memory.current minus easily reclaimed cache, as kubelet and
+    // `docker stats` report it
+    pub fn working_set(&self) -> u64 {
+        self.memory_current.saturating_sub(self.inactive_file)
+    }
+}
+
+pub fn read_stats(cgroup: &Cgroup) -> CgroupStats {
+    let keyed = |file: &str| cgroup.read(file).map(|c| parse_keyed(&c)).unwrap_or_default();
+    let memory_stat = keyed("memory.stat");
+    let events = keyed("memory.events");
+    let cpu_stat = keyed("cpu.stat");
+    let event = |key: &str| events.get(key).copied().unwrap_or(0);
+    let cpu = |key: &str| cpu_stat.get(key).copied().unwrap_or(0);
+
+    CgroupStats {
+        memory_current: cgroup.read("memory.current").ok().and_then(|c| c.trim().parse().ok()).unwrap_or(0),
+        memory_max: cgroup.read("memory.max").ok().and_then(|c| parse_limit(&c)),
+        memory_high: cgroup.read("memory.high").ok().and_then(|c| parse_limit(&c)),
+        inactive_file: memory_stat.get("inactive_file").copied().unwrap_or(0),
+        memory_events: MemoryEvents {
+            low: event("low"),
+            high: event("high"),
+            max: event("max"),
+            oom: event("oom"),
+            oom_kill: event("oom_kill"),
+        },
+        cpu_max: cgroup.read("cpu.max").ok().and_then(|c| parse_cpu_max(&c)).unwrap_or_default(),
+        cpu_stat: CpuStat {
+            usage_usec: cpu("usage_usec"),
+            user_usec: cpu("user_usec"),
+            system_usec: cpu("system_usec"),
+            nr_periods: cpu("nr_periods"),
+            nr_throttled: cpu("nr_throttled"),
+            throttled_usec: cpu("throttled_usec"),
+        },
+    }
+}
+
+// Every cgroup below `root` (not the root itself, which is the whole
+// host), parents before children
+pub fn walk(root: &Path) -> Vec<Cgroup> {
+    let mut cgroups = Vec::new();
+    walk_into(root, root, &mut cgroups);
+    cgroups
+}
+
+fn walk_into(root: &Path, dir: &Path, cgroups: &mut Vec<Cgroup>) {
+    let mut children: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())).map(|e| e.path()).collect(),
+        Err(_) => return,
+    };
+    children.sort();
+    for child in children {
+        let relative = child.strip_prefix(root).unwrap_or(&child);
+        cgroups.push(Cgroup { path: format!("/{}", relative.to_string_lossy()), dir: child.clone() });
+        walk_into(root, &child, cgroups);
+    }
+}
+
+// Pids directly in the cgroup (not in its children)
+pub fn read_procs(cgroup: &Cgroup) -> Vec<u32> {
+    cgroup.read("cgroup.procs")
+        .map(|content| content.lines().filter_map(|line| line.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Bytes read and written, summed over devices, from io.stat lines such
+// as "8:0 rbytes=1024 wbytes=2048 rios=1 wios=2 dbytes=0 dios=0"
+pub fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut totals = (0, 0);
+    for field in content.split_whitespace() {
+        match field.split_once('=') {
+            Some(("rbytes", value)) => totals.0 += value.parse::<u64>().unwrap_or(0),
+            Some(("wbytes", value)) => totals.1 += value.parse::<u64>().unwrap_or(0),
+            _ => {}
+        }
+    }
+    totals
+}
+
+pub fn read_io(cgroup: &Cgroup) -> (u64, u64) {
+    cgroup.read("io.stat").map(|c| parse_io_stat(&c)).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CgroupKind {
+    Slice,
+    Service,
+    Scope,
+    Container,
+    Pod,
+    Other,
+}
+
+impl CgroupKind {
+    // As in the JSON form, e.g. "container"
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CgroupKind::Slice => "slice",
+            CgroupKind::Service => "service",
+            CgroupKind::Scope => "scope",
+            CgroupKind::Container => "container",
+            CgroupKind::Pod => "pod",
+            CgroupKind::Other => "other",
+        }
+    }
+
+    // Groups that stand for a workload rather than an organizational level
+    pub fn is_workload(self) -> bool {
+        matches!(self, CgroupKind::Service | CgroupKind::Scope | CgroupKind::Container | CgroupKind::Pod)
+    }
+}
+
+// Runtime scope prefixes under the systemd cgroup driver
+const CONTAINER_SCOPE_PREFIXES: [&str; 4] = ["docker-", "cri-containerd-", "crio-", "libpod-"];
+
+// What a cgroup path stands for and a short name for it: the 12-character
+// id for containers, the uid for Kubernetes pods, the unit name otherwise.
+// Covers both the systemd and the cgroupfs drivers of docker and kubelet.
+pub fn classify(path: &str) -> (CgroupKind, String) {
+    let leaf = path.rsplit('/').next().unwrap_or(path);
+    let in_kubernetes = path.contains("kubepods");
+
+    let scope_id = leaf.strip_suffix(".scope").and_then(|stem| {
+        CONTAINER_SCOPE_PREFIXES.iter().find_map(|prefix| stem.strip_prefix(prefix)).filter(|id| is_container_id(id))
+    });
+    if let Some(id) = scope_id {
+        return (CgroupKind::Container, id[..12].to_string());
+    }
+    if is_container_id(leaf) && (in_kubernetes || path.contains("/docker/") || path.contains("libpod")) {
+        return (CgroupKind::Container, leaf[..12].to_string());
+    }
+    if in_kubernetes {
+        if let Some(uid) = pod_uid(leaf) {
+            return (CgroupKind::Pod, uid);
+        }
+    }
+
+    let kind = if leaf.ends_with(".service") {
+        CgroupKind::Service
+    } else if leaf.ends_with(".scope") {
+        CgroupKind::Scope
+    } else if leaf.ends_with(".slice") {
+        CgroupKind::Slice
+    } else {
+        CgroupKind::Other
+    };
+    (kind, leaf.to_string())
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// "pod1b2c-..." (cgroupfs) or "kubepods-burstable-pod1b2c_....slice"
+// (systemd, where the uid's dashes become underscores)
+fn pod_uid(leaf: &str) -> Option<String> {
+    if let Some(uid) = leaf.strip_prefix("pod") {
+        return Some(uid.to_string());
+    }
+    let stem = leaf.strip_suffix(".slice")?;
+    let uid = stem.rsplit('-').next()?.strip_prefix("pod")?;
+    Some(uid.replace('_', "-"))
+}
+
+// "key value" lines as in memory.stat, memory.events and cpu.stat
+pub fn parse_keyed(content: &str) -> HashMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// A byte limit, or None for "max"
+pub fn parse_limit(content: &str) -> Option<u64> {
+    content.trim().parse().ok()
+}
+
+pub fn parse_cpu_max(content: &str) -> Option<CpuMax> {
+    let mut fields = content.split_whitespace();
+    let quota = match fields.next()? {
+        "max" => None,
+        quota => Some(quota.parse().ok()?),
+    };
+    // The period is optional when writing but always shown when reading
+    let period = fields.next().map_or(Some(100_000), |p| p.parse().ok())?;
+    Some(CpuMax { quota, period })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, content: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(file), content).unwrap();
+    }
+
+    #[test]
+    fn test_detect_from_fake_root() {
+        let root = tempfile::tempdir().unwrap();
+        let hybrid = "4:memory:/docker/abc\n0::/docker/abc\n";
+        // Hybrid host: no controllers in the unified hierarchy
+        assert_eq!(detect(root.path(), hybrid), None);
+
+        write(root.path(), "cgroup.controllers", "cpu memory io pids\n");
+        let service = root.path().join("system.slice/web.service");
+        fs::create_dir_all(&service).unwrap();
+        let cgroup = detect(root.path(), "0::/system.slice/web.service\n").unwrap();
+        assert_eq!(cgroup.dir, service);
+        assert!(!cgroup.is_namespace_root());
+
+        // Namespaced container
+        let cgroup = detect(root.path(), "0::/\n").unwrap();
+        assert_eq!(cgroup.dir, root.path());
+        assert!(cgroup.is_namespace_root());
+        // Host path not visible from inside the container
+        assert_eq!(detect(root.path(), "0::/kubepods/pod1/ctr\n").unwrap().dir, root.path());
+        assert_eq!(detect(root.path(), "1:name=systemd:/\n"), None);
+    }
+
+    #[test]
+    fn test_read_stats() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path();
+        write(dir, "memory.current", "536870912\n");
+        write(dir, "memory.max", "1073741824\n");
+        write(dir, "memory.high", "max\n");
+        write(dir, "memory.stat", "anon 300000000\nfile 200000000\ninactive_file 100000000\n");
+        write(dir, "memory.events", "low 0\nhigh 0\nmax 12\noom 2\noom_kill 1\n");
+        write(dir, "cpu.max", "150000 100000\n");
+        write(dir, "cpu.stat", "usage_usec 5000000\nuser_usec 4000000\nsystem_usec 1000000\nnr_periods 400\nnr_throttled 40\nthrottled_usec 900000\n");
+
+        let stats = read_stats(&Cgroup { path: "/".to_string(), dir: dir.to_path_buf() });
+        assert_eq!(stats.memory_max, Some(1 << 30));
+        assert_eq!(stats.memory_high, None);
+        assert_eq!(stats.working_set(), 536870912 - 100000000);
+        assert_eq!((stats.memory_events.oom, stats.memory_events.oom_kill), (2, 1));
+        assert_eq!(stats.cpu_max.cores(), Some(1.5));
+        assert_eq!(stats.cpu_stat.nr_throttled, 40);
+
+        // Controllers not enabled: everything reads as unlimited and zero
+        let empty = read_stats(&Cgroup { path: "/".to_string(), dir: dir.join("missing") });
+        assert_eq!(empty, CgroupStats::default());
+        assert_eq!(parse_cpu_max("max 100000").unwrap().cores(), None);
+        assert_eq!(parse_cpu_max("50000").unwrap().cores(), Some(0.5));
+    }
+
+    #[test]
+    fn test_walk_and_io() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("system.slice/nginx.service"), "cgroup.procs", "100\n101\n");
+        write(&root.path().join("system.slice/nginx.service"), "io.stat",
+            "8:0 rbytes=1000 wbytes=2000 rios=1 wios=2 dbytes=0 dios=0\n8:16 rbytes=5 wbytes=0\n");
+        fs::create_dir_all(root.path().join("user.slice")).unwrap();
+
+        let cgroups = walk(root.path());
+        let paths: Vec<_> = cgroups.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["/system.slice", "/system.slice/nginx.service", "/user.slice"]);
+        assert_eq!(read_procs(&cgroups[1]), vec![100, 101]);
+        assert!(read_procs(&cgroups[0]).is_empty());
+        assert_eq!(read_io(&cgroups[1]), (1005, 2000));
+    }
+
+    #[test]
+    fn test_classify() {
+        let id = "4f1c9d0e2b7a".repeat(5) + "abcd";
+        let kind = |path: &str| classify(path);
+        assert_eq!(kind(&format!("/system.slice/docker-{}.scope", id)), (CgroupKind::Container, id[..12].to_string()));
+        assert_eq!(kind(&format!("/docker/{}", id)).0, CgroupKind::Container);
+        assert_eq!(
+            kind("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod12ab_34cd.slice"),
+            (CgroupKind::Pod, "12ab-34cd".to_string())
+        );
+        assert_eq!(kind("/kubepods/besteffort/pod12ab-34cd"), (CgroupKind::Pod, "12ab-34cd".to_string()));
+        assert_eq!(
+            kind(&format!("/kubepods/besteffort/pod12ab-34cd/{}", id)).0,
+            CgroupKind::Container
+        );
+        assert_eq!(
+            kind(&format!("/kubepods.slice/kubepods-pod1.slice/cri-containerd-{}.scope", id)).0,
+            CgroupKind::Container
+        );
+        assert_eq!(kind("/kubepods.slice/kubepods-burstable.slice").0, CgroupKind::Slice);
+        assert_eq!(kind("/system.slice/nginx.service"), (CgroupKind::Service, "nginx.service".to_string()));
+        assert_eq!(kind("/user.slice/user-1000.slice/session-2.scope").0, CgroupKind::Scope);
+        assert_eq!(kind("/init").0, CgroupKind::Other);
+
+        // as_str matches the JSON form
+        for kind in [CgroupKind::Slice, CgroupKind::Service, CgroupKind::Scope, CgroupKind::Container, CgroupKind::Pod, CgroupKind::Other] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+    }
+}
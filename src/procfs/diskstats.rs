@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+pub const DISKSTATS_PATH: &str = "/proc/diskstats";
+pub const SYS_BLOCK_PATH: &str = "/sys/block";
+
+// The kernel always reports diskstats sectors in 512-byte units,
+// regardless of the device's logical block size.
+pub const SECTOR_SIZE: u64 = 512;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskStat {
+    pub major: u32,
+    pub minor: u32,
+    pub name: String,
+    pub reads_completed: u64,
+    pub sectors_read: u64,
+    pub writes_completed: u64,
+    pub sectors_written: u64,
+}
+
+impl DiskStat {
+    pub fn read_bytes(&self) -> u64 {
+        self.sectors_read * SECTOR_SIZE
+    }
+
+    pub fn write_bytes(&self) -> u64 {
+        self.sectors_written * SECTOR_SIZE
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiskIo {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+}
+
+pub fn parse(content: &str) -> HashMap<String, DiskStat> {
+    content.lines().filter_map(parse_line).map(|s| (s.name.clone(), s)).collect()
+}
+
+fn parse_line(line: &str) -> Option<DiskStat> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    // major minor name + at least the 11 classic counters
+    if fields.len() < 14 {
+        return None;
+    }
+    let num = |i: usize| fields[i].parse::<u64>().ok();
+
+    Some(DiskStat {
+        major: fields[0].parse().ok()?,
+        minor: fields[1].parse().ok()?,
+        name: fields[2].to_string(),
+        reads_completed: num(3)?,
+        sectors_read: num(5)?,
+        writes_completed: num(7)?,
+        sectors_written: num(9)?,
+    })
+}
+
+// Map of device-mapper names ("vg0-root") to kernel names ("dm-0"),
+// read from /sys/block/dm-*/dm/name.
+pub fn read_dm_names(sys_block: &Path) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    let entries = match fs::read_dir(sys_block) {
+        Ok(entries) => entries,
+        Err(_) => return names,
+    };
+    for entry in entries.flatten() {
+        let kernel_name = entry.file_name().to_string_lossy().into_owned();
+        if !kernel_name.starts_with("dm-") {
+            continue;
+        }
+        if let Ok(name) = fs::read_to_string(entry.path().join("dm").join("name")) {
+            names.insert(name.trim().to_string(), kernel_name);
+        }
+    }
+    names
+}
+
+// Resolve the name sysinfo reports for a disk ("/dev/sda1",
+// "/dev/mapper/vg0-root", "overlay") to a diskstats device name.
+// Returns None for filesystems that are not backed by a block device.
+pub fn device_key(disk_name: &str, dm_names: &HashMap<String, String>) -> Option<String> {
+    let device = disk_name.strip_prefix("/dev/")?;
+    if let Some(mapped) = device.strip_prefix("mapper/") {
+        return dm_names.get(mapped).cloned();
+    }
+    device.rsplit('/').next().filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskStatsSample {
+    pub stats: HashMap<String, DiskStat>,
+    pub dm_names: HashMap<String, String>,
+    pub taken_at: Instant,
+}
+
+impl DiskStatsSample {
+    pub fn read() -> io::Result<Self> {
+        let content = fs::read_to_string(DISKSTATS_PATH)?;
+        Ok(Self::from_content(&content, read_dm_names(Path::new(SYS_BLOCK_PATH))))
+    }
+
+    pub fn from_content(content: &str, dm_names: HashMap<String, String>) -> Self {
+        Self {
+            stats: parse(content),
+            dm_names,
+            taken_at: Instant::now(),
+        }
+    }
+
+    // Look up the counters for a sysinfo disk name. A partition without a
+    // row of its own has none; the whole disk's I/O would not be its own.
+    pub fn lookup(&self, disk_name: &str) -> Option<&DiskStat> {
+        let key = device_key(disk_name, &self.dm_names)?;
+        self.stats.get(&key)
+    }
+
+    // Cumulative totals for the disk plus rates relative to `previous`.
+    // Counters that went backwards (device re-attached) yield a zero rate.
+    pub fn io(&self, disk_name: &str, previous: Option<&DiskStatsSample>) -> DiskIo {
+        let current = match self.lookup(disk_name) {
+            Some(stat) => stat,
+            None => return DiskIo::default(),
+        };
+        let mut io = DiskIo {
+            read_bytes: current.read_bytes(),
+            write_bytes: current.write_bytes(),
+            ..DiskIo::default()
+        };
+
+        if let Some((prev, prev_stat)) = previous.and_then(|p| p.lookup(disk_name).map(|s| (p, s))) {
+            let elapsed = self.taken_at.saturating_duration_since(prev.taken_at).as_secs_f64();
+            if elapsed > 0.0 {
+                io.read_bytes_per_sec =
+                    current.read_bytes().saturating_sub(prev_stat.read_bytes()) as f64 / elapsed;
+                io.write_bytes_per_sec =
+                    current.write_bytes().saturating_sub(prev_stat.write_bytes()) as f64 / elapsed;
+            }
+        }
+        io
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const FIXTURE: &str = "\
+   8       0 sda 120 3 2048 50 80 10 4096 70 0 100 120 0 0 0 0
+   8       1 sda1 100 3 1024 40 60 10 2048 60 0 90 100 0 0 0 0
+ 259       0 nvme0n1 500 0 8000 10 300 0 6000 20 0 30 30
+ 259       2 nvme0n1p2 400 0 7000 9 250 0 5000 18 0 25 27
+ 253       0 dm-0 42 0 800 5 21 0 400 3 0 8 8
+   7       0 loop0 broken line
+";
+
+    #[test]
+    fn test_parse_fixture() {
+        let stats = parse(FIXTURE);
+        assert_eq!(stats.len(), 5);
+        let sda1 = &stats["sda1"];
+        assert_eq!((sda1.major, sda1.minor), (8, 1));
+        assert_eq!(sda1.read_bytes(), 1024 * SECTOR_SIZE);
+        assert_eq!(sda1.write_bytes(), 2048 * SECTOR_SIZE);
+    }
+
+    #[test]
+    fn test_device_mapping() {
+        let dm: HashMap<_, _> = [("vg0-root".to_string(), "dm-0".to_string())].into();
+        assert_eq!(device_key("/dev/sda1", &dm).as_deref(), Some("sda1"));
+        assert_eq!(device_key("/dev/mapper/vg0-root", &dm).as_deref(), Some("dm-0"));
+        assert_eq!(device_key("overlay", &dm), None);
+
+        let sample = DiskStatsSample::from_content(FIXTURE, dm);
+        assert_eq!(sample.lookup("/dev/mapper/vg0-root").unwrap().sectors_read, 800);
+        // nvme0n1p1 has no row; the whole disk's counters are not its own
+        assert!(sample.lookup("/dev/nvme0n1p1").is_none());
+        assert_eq!(sample.lookup("/dev/nvme0n1").unwrap().name, "nvme0n1");
+    }
+
+    #[test]
+    fn test_rates_between_samples() {
+        let mut prev = DiskStatsSample::from_content(FIXTURE, HashMap::new());
+        prev.taken_at -= Duration::from_secs(2);
+        let next = FIXTURE.replace("sda1 100 3 1024", "sda1 100 3 3072");
+        let cur = DiskStatsSample::from_content(&next, HashMap::new());
+
+        let io = cur.io("/dev/sda1", Some(&prev));
+        assert_eq!(io.read_bytes, 3072 * SECTOR_SIZE);
+        let expected = (2048 * SECTOR_SIZE) as f64 / 2.0;
+        assert!((io.read_bytes_per_sec - expected).abs() < expected * 0.01);
+        assert_eq!(io.write_bytes_per_sec, 0.0);
+        assert_eq!(cur.io("overlay", Some(&prev)), DiskIo::default());
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+pub const MEMINFO_PATH: &str = "/proc/meminfo";
+
+// The /proc/meminfo fields we report. Sizes are in bytes; the HugePages_*
+// fields are page counts, as in the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_cached: u64,
+    pub shared: u64,
+    pub slab: u64,
+    pub slab_reclaimable: u64,
+    pub dirty: u64,
+    pub writeback: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    pub committed_as: u64,
+    pub commit_limit: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub hugepage_size: u64,
+}
+
+impl MemInfo {
+    pub fn read() -> io::Result<Self> {
+        Ok(parse(&fs::read_to_string(MEMINFO_PATH)?))
+    }
+
+    pub fn swap_used(&self) -> u64 {
+        self.swap_total.saturating_sub(self.swap_free)
+    }
+}
+
+// Every "Key: value [kB]" line, with kB values converted to bytes
+pub fn parse_fields(content: &str) -> HashMap<&str, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let mut parts = rest.split_whitespace();
+            let value: u64 = parts.next()?.parse().ok()?;
+            let value = match parts.next() {
+                Some("kB") => value * 1024,
+                _ => value,
+            };
+            Some((key.trim(), value))
+        })
+        .collect()
+}
+
+// Fields missing on older kernels (or without CONFIG_HUGETLBFS) read as zero
+pub fn parse(content: &str) -> MemInfo {
+    let fields = parse_fields(content);
+    let field = |key: &str| fields.get(key).copied().unwrap_or(0);
+    MemInfo {
+        total: field("MemTotal"),
+        free: field("MemFree"),
+        available: field("MemAvailable"),
+        buffers: field("Buffers"),
+        cached: field("Cached"),
+        swap_cached: field("SwapCached"),
+        shared: field("Shmem"),
+        slab: field("Slab"),
+        slab_reclaimable: field("SReclaimable"),
+        dirty: field("Dirty"),
+        writeback: field("Writeback"),
+        swap_total: field("SwapTotal"),
+        swap_free: field("SwapFree"),
+        committed_as: field("Committed_AS"),
+        commit_limit: field("CommitLimit"),
+        hugepages_total: field("HugePages_Total"),
+        hugepages_free: field("HugePages_Free"),
+        hugepage_size: field("Hugepagesize"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+MemTotal:       16318000 kB
+MemFree:         1200000 kB
+MemAvailable:    9000000 kB
+Buffers:          300000 kB
+Cached:          7000000 kB
+SwapCached:        10000 kB
+SwapTotal:       4194300 kB
+SwapFree:        4000000 kB
+Dirty:              1234 kB
+Writeback:             0 kB
+Shmem:            500000 kB
+Slab:             600000 kB
+SReclaimable:     450000 kB
+CommitLimit:    12353300 kB
+Committed_AS:   20000000 kB
+HugePages_Total:      16
+HugePages_Free:        4
+Hugepagesize:       2048 kB
+";
+
+    #[test]
+    fn test_parse_fixture() {
+        let info = parse(FIXTURE);
+        assert_eq!(info.total, 16318000 * 1024);
+        assert_eq!(info.dirty, 1234 * 1024);
+        assert_eq!(info.swap_used(), 194300 * 1024);
+        // Overcommitted: committed exceeds the limit
+        assert!(info.committed_as > info.commit_limit);
+        // Page counts are not scaled
+        assert_eq!((info.hugepages_total, info.hugepages_free), (16, 4));
+        assert_eq!(info.hugepage_size, 2 * 1024 * 1024);
+
+        let minimal = parse("MemTotal: 1024 kB\ngarbage\nMemFree:\n");
+        assert_eq!(minimal.total, 1024 * 1024);
+        assert_eq!(minimal.free, 0);
+        assert_eq!(minimal.swap_used(), 0);
+    }
+}
@@ -0,0 +1,12 @@
+// Parsers for the Linux /proc and /sys text interfaces (including cgroupfs).
+// Every parser works on plain text so it can be tested against fixtures.
+pub mod cgroup;
+pub mod diskstats;
+pub mod meminfo;
+pub mod netdev;
+pub mod pressure;
+pub mod smaps;
+pub mod sockets;
+pub mod stat;
+pub mod status;
+pub mod vmstat;
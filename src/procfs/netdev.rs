@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+pub const NET_DEV_PATH: &str = "/proc/net/dev";
+
+// Cumulative counters for one interface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceStat {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterfaceRates {
+    pub rx_bytes: f64,
+    pub tx_bytes: f64,
+    pub rx_packets: f64,
+    pub tx_packets: f64,
+}
+
+pub fn parse(content: &str) -> BTreeMap<String, InterfaceStat> {
+    content.lines().filter_map(parse_line).map(|s| (s.name.clone(), s)).collect()
+}
+
+// "  eth0: 1234 56 0 0 0 0 0 0 7890 12 0 0 0 0 0 0"; the two header
+// lines have no number after the colon and are skipped
+fn parse_line(line: &str) -> Option<InterfaceStat> {
+    let (name, counters) = line.split_once(':')?;
+    let fields = counters.split_whitespace().map(|f| f.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    if fields.len() < 16 {
+        return None;
+    }
+    Some(InterfaceStat {
+        name: name.trim().to_string(),
+        rx_bytes: fields[0],
+        rx_packets: fields[1],
+        rx_errors: fields[2],
+        rx_dropped: fields[3],
+        tx_bytes: fields[8],
+        tx_packets: fields[9],
+        tx_errors: fields[10],
+        tx_dropped: fields[11],
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct NetDevSample {
+    pub stats: BTreeMap<String, InterfaceStat>,
+    pub taken_at: Instant,
+}
+
+impl NetDevSample {
+    pub fn read() -> io::Result<Self> {
+        Ok(Self::from_content(&fs::read_to_string(NET_DEV_PATH)?))
+    }
+
+    pub fn from_content(content: &str) -> Self {
+        Self::from_stats(parse(content))
+    }
+
+    // For counters from another source, e.g. sysinfo where there is no procfs
+    pub fn from_stats(stats: BTreeMap<String, InterfaceStat>) -> Self {
+        Self { stats, taken_at: Instant::now() }
+    }
+
+    // Rates for `name` relative to `previous`. A new interface, or one
+    // whose counters went backwards (re-created), gets zero rates.
+    pub fn rates(&self, name: &str, previous: &NetDevSample) -> InterfaceRates {
+        let (current, before) = match (self.stats.get(name), previous.stats.get(name)) {
+            (Some(current), Some(before)) => (current, before),
+            _ => return InterfaceRates::default(),
+        };
+        let elapsed = self.taken_at.saturating_duration_since(previous.taken_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return InterfaceRates::default();
+        }
+        let rate = |now: u64, then: u64| now.saturating_sub(then) as f64 / elapsed;
+        InterfaceRates {
+            rx_bytes: rate(current.rx_bytes, before.rx_bytes),
+            tx_bytes: rate(current.tx_bytes, before.tx_bytes),
+            rx_packets: rate(current.rx_packets, before.rx_packets),
+            tx_packets: rate(current.tx_packets, before.tx_packets),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const FIXTURE: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  104560     820    0    0    0     0          0         0   104560     820    0    0    0     0       0          0
+  eth0: 9876543   12000    3   17    0     0          0        40  1234567    9000    0    2    0     0       0          0
+veth1a2b:    100       1    0    0    0     0          0         0      200       2    0    0    0     0       0          0
+";
+
+    #[test]
+    fn test_parse_fixture() {
+        let stats = parse(FIXTURE);
+        assert_eq!(stats.keys().collect::<Vec<_>>(), vec!["eth0", "lo", "veth1a2b"]);
+        let eth0 = &stats["eth0"];
+        assert_eq!((eth0.rx_bytes, eth0.rx_packets, eth0.rx_errors, eth0.rx_dropped), (9876543, 12000, 3, 17));
+        assert_eq!((eth0.tx_bytes, eth0.tx_packets, eth0.tx_dropped), (1234567, 9000, 2));
+    }
+
+    #[test]
+    fn test_rates_between_samples() {
+        let mut prev = NetDevSample::from_content(FIXTURE);
+        prev.taken_at -= Duration::from_secs(2);
+        let next = FIXTURE.replace("eth0: 9876543   12000", "eth0: 9878543   12010");
+        let cur = NetDevSample::from_content(&next);
+
+        let rates = cur.rates("eth0", &prev);
+        assert!((rates.rx_bytes - 1000.0).abs() < 10.0);
+        assert!((rates.rx_packets - 5.0).abs() < 0.1);
+        assert_eq!(rates.tx_bytes, 0.0);
+        assert_eq!(cur.rates("wlan0", &prev), InterfaceRates::default());
+        // Re-created interface: counters restart from zero
+        let reset = NetDevSample::from_content(&FIXTURE.replace("eth0: 9876543", "eth0: 5"));
+        assert_eq!(reset.rates("eth0", &prev).rx_bytes, 0.0);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Serialize;
+
+pub const PRESSURE_DIR: &str = "/proc/pressure";
+
+// One line of a PSI file: the share of time some (or all) non-idle tasks
+// were stalled, in percent over 10s/60s/300s windows, and the cumulative
+// stall time in microseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PressureLine {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    pub total: u64,
+}
+
+// `full` is all zero for cpu on kernels that report it at all; before
+// 5.13 the cpu file has no full line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Pressure {
+    pub some: PressureLine,
+    pub full: PressureLine,
+}
+
+// `resource` is "cpu", "memory" or "io". `dir` is /proc/pressure, with
+// files named after the resource, or a cgroup v2 directory, with
+// cpu.pressure and so on. Fails without CONFIG_PSI or when it is
+// disabled with psi=0.
+pub fn read(dir: &Path, resource: &str) -> io::Result<Pressure> {
+    let content = match fs::read_to_string(dir.join(resource)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => fs::read_to_string(dir.join(format!("{}.pressure", resource)))?,
+        result => result?,
+    };
+    parse(&content).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("malformed pressure file {}", resource)))
+}
+
+pub fn parse(content: &str) -> Option<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("some") => some = parse_line(fields),
+            Some("full") => full = parse_line(fields),
+            _ => {}
+        }
+    }
+    Some(Pressure { some: some?, full: full.unwrap_or_default() })
+}
+
+fn parse_line<'a>(fields: impl Iterator<Item = &'a str>) -> Option<PressureLine> {
+    let mut line = PressureLine::default();
+    for field in fields {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "avg10" => line.avg10 = value.parse().ok()?,
+            "avg60" => line.avg60 = value.parse().ok()?,
+            "avg300" => line.avg300 = value.parse().ok()?,
+            "total" => line.total = value.parse().ok()?,
+            _ => {}
+        }
+    }
+    Some(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixtures() {
+        let memory = parse("\
+some avg10=12.50 avg60=4.00 avg300=1.25 total=98765432
+full avg10=3.00 avg60=1.00 avg300=0.10 total=1234567
+").unwrap();
+        assert_eq!(memory.some.avg10, 12.5);
+        assert_eq!(memory.some.total, 98765432);
+        assert_eq!(memory.full.avg300, 0.1);
+
+        // Pre-5.13 cpu file
+        let cpu = parse("some avg10=0.50 avg60=0.20 avg300=0.05 total=4242\n").unwrap();
+        assert_eq!(cpu.some.avg60, 0.2);
+        assert_eq!(cpu.full, PressureLine::default());
+
+        assert_eq!(parse("some avg10=oops\n"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_read_cgroup_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("io.pressure"), "some avg10=1.00 avg60=0.00 avg300=0.00 total=7\n").unwrap();
+        assert_eq!(read(dir.path(), "io").unwrap().some.total, 7);
+        assert_eq!(read(dir.path(), "cpu").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+
+pub const NET_DIR: &str = "/proc/net";
+pub const PROC_ROOT: &str = "/proc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 4] = [Protocol::Tcp, Protocol::Tcp6, Protocol::Udp, Protocol::Udp6];
+
+    // File name under /proc/net
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+        }
+    }
+
+    pub fn is_tcp(self) -> bool {
+        matches!(self, Protocol::Tcp | Protocol::Tcp6)
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.file_name())
+    }
+}
+
+// Socket states as numbered in include/net/tcp_states.h. UDP sockets use
+// Established when connected and Close otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TcpState {
+    Established,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+    NewSynRecv,
+}
+
+impl TcpState {
+    pub fn from_code(code: u8) -> Option<Self> {
+        use TcpState::*;
+        [Established, SynSent, SynRecv, FinWait1, FinWait2, TimeWait, Close, CloseWait, LastAck, Listen, Closing, NewSynRecv]
+            .get(usize::from(code).checked_sub(1)?)
+            .copied()
+    }
+
+    // The names `ss` and netstat use
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TcpState::Established => "ESTABLISHED",
+            TcpState::SynSent => "SYN_SENT",
+            TcpState::SynRecv => "SYN_RECV",
+            TcpState::FinWait1 => "FIN_WAIT1",
+            TcpState::FinWait2 => "FIN_WAIT2",
+            TcpState::TimeWait => "TIME_WAIT",
+            TcpState::Close => "CLOSE",
+            TcpState::CloseWait => "CLOSE_WAIT",
+            TcpState::LastAck => "LAST_ACK",
+            TcpState::Listen => "LISTEN",
+            TcpState::Closing => "CLOSING",
+            TcpState::NewSynRecv => "NEW_SYN_RECV",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socket {
+    pub protocol: Protocol,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub state: TcpState,
+    pub uid: u32,
+    // Zero for sockets no longer owned by a process (e.g. TIME_WAIT)
+    pub inode: u64,
+}
+
+impl Socket {
+    // TCP sockets in LISTEN and bound UDP sockets without a peer
+    pub fn is_listening(&self) -> bool {
+        if self.protocol.is_tcp() {
+            self.state == TcpState::Listen
+        } else {
+            self.state == TcpState::Close && self.remote.port() == 0
+        }
+    }
+}
+
+pub fn parse(protocol: Protocol, content: &str) -> Vec<Socket> {
+    // The first line is the column header
+    content.lines().skip(1).filter_map(|line| parse_line(protocol, line)).collect()
+}
+
+fn parse_line(protocol: Protocol, line: &str) -> Option<Socket> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return None;
+    }
+    Some(Socket {
+        protocol,
+        local: parse_address(fields[1])?,
+        remote: parse_address(fields[2])?,
+        state: TcpState::from_code(u8::from_str_radix(fields[3], 16).ok()?)?,
+        uid: fields[7].parse().ok()?,
+        inode: fields[9].parse().ok()?,
+    })
+}
+
+// "0100007F:0016" is 127.0.0.1:22. The kernel prints the address as
+// 32-bit words in host byte order, so the bytes come back with to_ne_bytes.
+fn parse_address(field: &str) -> Option<SocketAddr> {
+    let (address, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut words = Vec::with_capacity(4);
+    for i in (0..address.len()).step_by(8) {
+        words.push(u32::from_str_radix(address.get(i..i + 8)?, 16).ok()?);
+    }
+    let ip = match words.as_slice() {
+        [word] => IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes())),
+        [a, b, c, d] => {
+            let mut bytes = [0u8; 16];
+            for (chunk, word) in bytes.chunks_mut(4).zip([a, b, c, d]) {
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            let ip = Ipv6Addr::from(bytes);
+            // IPv4 clients of dual-stack sockets show up as ::ffff:a.b.c.d
+            ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4)
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+// Every socket of the four protocols; missing files (no IPv6) are skipped
+pub fn read_all(net_dir: &Path) -> Vec<Socket> {
+    Protocol::ALL
+        .iter()
+        .filter_map(|&protocol| {
+            let content = fs::read_to_string(net_dir.join(protocol.file_name())).ok()?;
+            Some(parse(protocol, &content))
+        })
+        .flatten()
+        .collect()
+}
+
+// Map of socket inode to the pid holding it, from the /proc/<pid>/fd links
+// ("socket:[12345]"). Processes of other users are only visible to root.
+pub fn socket_owners(proc_root: &Path) -> io::Result<HashMap<u64, u32>> {
+    let mut owners = HashMap::new();
+    for entry in fs::read_dir(proc_root)?.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        for fd in fds.flatten() {
+            let target = match fs::read_link(fd.path()) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            if let Some(inode) = socket_inode(&target.to_string_lossy()) {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    Ok(owners)
+}
+
+fn socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0F02000A:0016 0202000A:D431 01 00000000:00000000 02:000A7B37 00000000     0        0 5151 4 0000000000000000 20 4 30 10 -1
+   2: 0F02000A:0016 0302000A:D432 06 00000000:00000000 03:00001770 00000000     0        0 0 3 0000000000000000
+";
+
+    const TCP6: &str = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000000000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 6161 1 0000000000000000 100 0 0 10 0
+   1: 0000000000000000FFFF00000100007F:1F90 0000000000000000FFFF00000100007F:E2A4 01 00000000:00000000 00:00000000 00000000     0        0 7171 1 0000000000000000 20 0 0 10 -1
+";
+
+    const UDP: &str = "\
+   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  100: 00000000:0044 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 8181 2 0000000000000000 0
+";
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn test_parse_fixtures() {
+        let tcp = parse(Protocol::Tcp, TCP);
+        assert_eq!(tcp.len(), 3);
+        assert_eq!(tcp[0].local, "127.0.0.1:3306".parse().unwrap());
+        assert!(tcp[0].is_listening());
+        assert_eq!((tcp[0].uid, tcp[0].inode), (999, 4242));
+        assert_eq!(tcp[1].state, TcpState::Established);
+        assert_eq!(tcp[1].remote, "10.0.2.2:54321".parse().unwrap());
+        assert_eq!(tcp[2].state.as_str(), "TIME_WAIT");
+
+        let tcp6 = parse(Protocol::Tcp6, TCP6);
+        assert_eq!(tcp6[0].local, "[::]:8080".parse().unwrap());
+        // v4-mapped peers are reported as plain IPv4
+        assert_eq!(tcp6[1].remote.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let udp = parse(Protocol::Udp, UDP);
+        assert!(udp[0].is_listening());
+        assert_eq!(udp[0].local.port(), 68);
+    }
+
+    #[test]
+    fn test_states_and_inodes() {
+        assert_eq!(TcpState::from_code(0x0A), Some(TcpState::Listen));
+        assert_eq!(TcpState::from_code(0), None);
+        assert_eq!(TcpState::from_code(0x0D), None);
+        assert_eq!(socket_inode("socket:[12345]"), Some(12345));
+        assert_eq!(socket_inode("pipe:[12345]"), None);
+        assert!(parse(Protocol::Tcp, "header\n   0: nonsense\n").is_empty());
+    }
+
+    // Needs a Linux /proc
+    #[test]
+    #[ignore]
+    fn test_live_socket_owner() {
+        // Our own listener shows up with our pid
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sockets = read_all(Path::new(NET_DIR));
+        let ours = sockets.iter().find(|s| s.is_listening() && s.local.port() == port).unwrap();
+        let owners = socket_owners(Path::new(PROC_ROOT)).unwrap();
+        assert_eq!(owners.get(&ours.inode), Some(&std::process::id()));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use serde::Serialize;
+
+pub const STAT_PATH: &str = "/proc/stat";
+
+// Cumulative time spent in each state, in USER_HZ ticks. `user` and
+// `nice` already include guest time, so guest columns are not kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+// Share of time spent in each state over an interval, in percent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CpuBreakdown {
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+}
+
+impl CpuBreakdown {
+    // Everything but idle and iowait
+    pub fn busy(&self) -> f64 {
+        100.0 - self.idle - self.iowait
+    }
+}
+
+impl CpuTimes {
+    pub fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle + self.iowait + self.irq + self.softirq + self.steal
+    }
+
+    // Breakdown of the time elapsed since `previous`. Counters that went
+    // backwards (a CPU taken offline and back) count as zero, and an
+    // interval without any ticks gives an all-zero breakdown.
+    pub fn breakdown_since(&self, previous: &CpuTimes) -> CpuBreakdown {
+        let delta = CpuTimes {
+            user: self.user.saturating_sub(previous.user),
+            nice: self.nice.saturating_sub(previous.nice),
+            system: self.system.saturating_sub(previous.system),
+            idle: self.idle.saturating_sub(previous.idle),
+            iowait: self.iowait.saturating_sub(previous.iowait),
+            irq: self.irq.saturating_sub(previous.irq),
+            softirq: self.softirq.saturating_sub(previous.softirq),
+            steal: self.steal.saturating_sub(previous.steal),
+        };
+        let total = delta.total();
+        if total == 0 {
+            return CpuBreakdown::default();
+        }
+        let share = |ticks: u64| ticks as f64 / total as f64 * 100.0;
+        CpuBreakdown {
+            user: share(delta.user),
+            nice: share(delta.nice),
+            system: share(delta.system),
+            idle: share(delta.idle),
+            iowait: share(delta.iowait),
+            irq: share(delta.irq),
+            softirq: share(delta.softirq),
+            steal: share(delta.steal),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatSample {
+    // The aggregate "cpu" line
+    pub total: CpuTimes,
+    // Per-CPU lines keyed by name ("cpu0"); offline CPUs have no line
+    pub cores: BTreeMap<String, CpuTimes>,
+}
+
+impl StatSample {
+    pub fn read() -> io::Result<Self> {
+        Ok(parse(&fs::read_to_string(STAT_PATH)?))
+    }
+}
+
+pub fn parse(content: &str) -> StatSample {
+    let mut sample = StatSample::default();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let name = match fields.next() {
+            Some(name) if name.starts_with("cpu") => name,
+            _ => continue,
+        };
+        let times = match parse_times(fields) {
+            Some(times) => times,
+            None => continue,
+        };
+        if name == "cpu" {
+            sample.total = times;
+        } else {
+            sample.cores.insert(name.to_string(), times);
+        }
+    }
+    sample
+}
+
+// Kernels before 2.6.11 stop after softirq and before 2.6 after idle;
+// missing columns read as zero.
+fn parse_times<'a>(fields: impl Iterator<Item = &'a str>) -> Option<CpuTimes> {
+    let values = fields.map(|f| f.parse::<u64>().ok()).collect::<Option<Vec<_>>>()?;
+    if values.len() < 4 {
+        return None;
+    }
+    let value = |i: usize| values.get(i).copied().unwrap_or(0);
+    Some(CpuTimes {
+        user: value(0),
+        nice: value(1),
+        system: value(2),
+        idle: value(3),
+        iowait: value(4),
+        irq: value(5),
+        softirq: value(6),
+        steal: value(7),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = "\
+cpu  1000 50 300 8000 400 20 30 200 100 0
+cpu0 500 25 150 4000 200 10 15 100 50 0
+cpu1 500 25 150 4000 200 10 15 100 50 0
+intr 123456 0 0
+ctxt 987654
+btime 1700000000
+processes 4242
+procs_running 2
+";
+
+    #[test]
+    fn test_parse_fixture() {
+        let sample = parse(FIXTURE);
+        assert_eq!(sample.total.user, 1000);
+        assert_eq!(sample.total.steal, 200);
+        assert_eq!(sample.total.total(), 10000);
+        assert_eq!(sample.cores.keys().collect::<Vec<_>>(), vec!["cpu0", "cpu1"]);
+
+        let old_kernel = parse("cpu 10 0 5 85\ncpu0 bogus line\n");
+        assert_eq!(old_kernel.total.idle, 85);
+        assert_eq!(old_kernel.total.iowait, 0);
+        assert!(old_kernel.cores.is_empty());
+    }
+
+    #[test]
+    fn test_breakdown_between_samples() {
+        let before = parse(FIXTURE).total;
+        let after = CpuTimes { user: 1100, iowait: 450, steal: 250, idle: 8300, ..before };
+        let breakdown = after.breakdown_since(&before);
+        // 100 + 50 + 50 + 300 ticks elapsed
+        assert_eq!(breakdown.user, 20.0);
+        assert_eq!(breakdown.iowait, 10.0);
+        assert_eq!(breakdown.steal, 10.0);
+        assert_eq!(breakdown.idle, 60.0);
+        assert_eq!(breakdown.busy(), 30.0);
+
+        assert_eq!(before.breakdown_since(&before), CpuBreakdown::default());
+        // Counters reset: nothing goes negative
+        assert_eq!(CpuTimes::default().breakdown_since(&before), CpuBreakdown::default());
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use super::meminfo::parse_fields;
+
+// Memory and thread counts from /proc/<pid>/status, in bytes. Unlike
+// smaps_rollup this is readable for every process. Kernel threads have
+// no Vm*/Rss* lines, so those read as zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcStatus {
+    pub vm_rss: u64,
+    // Peak resident set size
+    pub vm_hwm: u64,
+    pub rss_anon: u64,
+    pub rss_file: u64,
+    pub rss_shmem: u64,
+    pub vm_swap: u64,
+    pub threads: u64,
+}
+
+impl ProcStatus {
+    pub fn read(proc_root: &Path, pid: u32) -> io::Result<Self> {
+        Ok(parse(&fs::read_to_string(proc_root.join(pid.to_string()).join("status"))?))
+    }
+}
+
+pub fn parse(content: &str) -> ProcStatus {
+    let fields = parse_fields(content);
+    let field = |key: &str| fields.get(key).copied().unwrap_or(0);
+    ProcStatus {
+        vm_rss: field("VmRSS"),
+        vm_hwm: field("VmHWM"),
+        rss_anon: field("RssAnon"),
+        rss_file: field("RssFile"),
+        rss_shmem: field("RssShmem"),
+        vm_swap: field("VmSwap"),
+        threads: field("Threads"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixture() {
+        let status = parse("\
+Name:\tpostgres
+State:\tS (sleeping)
+Pid:\t1234
+VmPeak:\t  320000 kB
+VmHWM:\t   65536 kB
+VmRSS:\t   40960 kB
+RssAnon:\t   8192 kB
+RssFile:\t  16384 kB
+RssShmem:\t  16384 kB
+VmSwap:\t    1024 kB
+Threads:\t8
+");
+        assert_eq!(status.vm_rss, 40960 * 1024);
+        assert_eq!(status.vm_hwm, 64 * 1024 * 1024);
+        assert_eq!(status.rss_anon + status.rss_file + status.rss_shmem, status.vm_rss);
+        assert_eq!(status.vm_swap, 1024 * 1024);
+        assert_eq!(status.threads, 8);
+
+        // kthreadd
+        assert_eq!(parse("Name:\tkthreadd\nThreads:\t1\n").vm_rss, 0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::Instant;
+
+pub const VMSTAT_PATH: &str = "/proc/vmstat";
+
+// Cumulative event counters from /proc/vmstat. Swap counters are in pages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VmStat {
+    pub pswpin: u64,
+    pub pswpout: u64,
+    pub pgfault: u64,
+    pub pgmajfault: u64,
+    // Processes killed by the OOM killer, host-wide (Linux 4.13+)
+    pub oom_kill: u64,
+}
+
+// Per-second rates of the counters in `VmStat`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VmRates {
+    pub pswpin: f64,
+    pub pswpout: f64,
+    pub pgfault: f64,
+    pub pgmajfault: f64,
+}
+
+pub fn parse_fields(content: &str) -> HashMap<&str, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key, value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+pub fn parse(content: &str) -> VmStat {
+    let fields = parse_fields(content);
+    let field = |key: &str| fields.get(key).copied().unwrap_or(0);
+    VmStat {
+        pswpin: field("pswpin"),
+        pswpout: field("pswpout"),
+        pgfault: field("pgfault"),
+        pgmajfault: field("pgmajfault"),
+        oom_kill: field("oom_kill"),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VmStatSample {
+    pub stat: VmStat,
+    pub taken_at: Instant,
+}
+
+impl VmStatSample {
+    pub fn read() -> io::Result<Self> {
+        Ok(Self::from_content(&fs::read_to_string(VMSTAT_PATH)?))
+    }
+
+    pub fn from_content(content: &str) -> Self {
+        Self {
+            stat: parse(content),
+            taken_at: Instant::now(),
+        }
+    }
+
+    // Rates relative to `previous`; counters that went backwards give zero
+    pub fn rates(&self, previous: &VmStatSample) -> VmRates {
+        let elapsed = self.taken_at.saturating_duration_since(previous.taken_at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return VmRates::default();
+        }
+        let rate = |current: u64, before: u64| current.saturating_sub(before) as f64 / elapsed;
+        let (cur, prev) = (&self.stat, &previous.stat);
+        VmRates {
+            pswpin: rate(cur.pswpin, prev.pswpin),
+            pswpout: rate(cur.pswpout, prev.pswpout),
+            pgfault: rate(cur.pgfault, prev.pgfault),
+            pgmajfault: rate(cur.pgmajfault, prev.pgmajfault),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const FIXTURE: &str = "\
+nr_free_pages 150000
+pswpin 100
+pswpout 250
+pgfault 30111685
+pgmajfault 2069
+oom_kill 2
+";
+
+    #[test]
+    fn test_parse_and_rates() {
+        let mut prev = VmStatSample::from_content(FIXTURE);
+        assert_eq!(prev.stat.pswpout, 250);
+        assert_eq!(prev.stat.pgmajfault, 2069);
+        assert_eq!(prev.stat.oom_kill, 2);
+        assert_eq!(parse("pswpin\nbogus x\n"), VmStat::default());
+
+        prev.taken_at -= Duration::from_secs(4);
+        let next = FIXTURE.replace("pswpout 250", "pswpout 650").replace("pgmajfault 2069", "pgmajfault 2000");
+        let rates = VmStatSample::from_content(&next).rates(&prev);
+        assert!((rates.pswpout - 100.0).abs() < 1.0);
+        assert_eq!(rates.pswpin, 0.0);
+        // Went backwards
+        assert_eq!(rates.pgmajfault, 0.0);
+    }
+}
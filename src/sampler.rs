@@ -0,0 +1,219 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use log::warn;
+use sysinfo::{System, SystemExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use crate::collector::CollectorRegistry;
+use crate::config::Config;
+use crate::monitor::DetailedMetrics;
+
+pub type Snapshot = Arc<DetailedMetrics>;
+
+// Samples buffered for slow subscribers before they start skipping
+const CHANNEL_CAPACITY: usize = 16;
+// Upper bound on how long stop/interval changes take to be noticed
+const POLL_SLICE: Duration = Duration::from_millis(100);
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+// Owns the collectors and the sysinfo handle. Once spawned, it is the only
+// place where collection happens; everyone else reads published snapshots.
+pub struct Sampler {
+    registry: CollectorRegistry,
+    system: System,
+    interval: Duration,
+}
+
+impl Sampler {
+    pub fn new(registry: CollectorRegistry, interval: Duration) -> Self {
+        Self {
+            registry,
+            system: System::new(),
+            interval,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            CollectorRegistry::from_config(config),
+            Duration::from_secs(config.update_interval),
+        )
+    }
+
+    // Start sampling on a dedicated thread
+    pub fn spawn(self) -> io::Result<SamplerHandle> {
+        let (latest_tx, latest_rx) = watch::channel(None);
+        let (updates, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let shared = Arc::new(Shared {
+            interval_ms: AtomicU64::new(self.interval.max(MIN_INTERVAL).as_millis() as u64),
+            running: AtomicBool::new(true),
+            updates: Mutex::new(Some(updates)),
+        });
+
+        let thread_shared = shared.clone();
+        let Sampler { mut registry, mut system, .. } = self;
+        thread::Builder::new().name("sampler".to_string()).spawn(move || {
+            while thread_shared.running.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                let snapshot = Arc::new(registry.collect(&mut system));
+                latest_tx.send_replace(Some(snapshot.clone()));
+                thread_shared.publish(snapshot);
+
+                while thread_shared.running.load(Ordering::Relaxed) {
+                    let remaining = thread_shared.interval().saturating_sub(started.elapsed());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    thread::sleep(remaining.min(POLL_SLICE));
+                }
+            }
+            thread_shared.close();
+        })?;
+
+        Ok(SamplerHandle { shared, latest: latest_rx })
+    }
+}
+
+struct Shared {
+    interval_ms: AtomicU64,
+    running: AtomicBool,
+    // Taken on stop so subscribers see the channel close
+    updates: Mutex<Option<broadcast::Sender<Snapshot>>>,
+}
+
+impl Shared {
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.load(Ordering::Relaxed))
+    }
+
+    fn publish(&self, snapshot: Snapshot) {
+        if let Some(updates) = self.updates.lock().unwrap().as_ref() {
+            // No subscribers is not an error
+            let _ = updates.send(snapshot);
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Snapshot> {
+        match self.updates.lock().unwrap().as_ref() {
+            Some(updates) => updates.subscribe(),
+            // Already stopped: hand out a receiver that is closed right away
+            None => broadcast::channel(1).1,
+        }
+    }
+
+    fn close(&self) {
+        self.updates.lock().unwrap().take();
+    }
+}
+
+// Cheap to clone; every consumer (web, TUI, storage, alerts) gets its own.
+#[derive(Clone)]
+pub struct SamplerHandle {
+    shared: Arc<Shared>,
+    latest: watch::Receiver<Option<Snapshot>>,
+}
+
+impl SamplerHandle {
+    // Most recent sample, or None before the first collection finished
+    pub fn latest(&self) -> Option<Snapshot> {
+        self.latest.borrow().clone()
+    }
+
+    // Receiver that is notified whenever a new sample is published
+    pub fn watch(&self) -> watch::Receiver<Option<Snapshot>> {
+        self.latest.clone()
+    }
+
+    // Stream of every sample; slow receivers skip ahead instead of blocking
+    pub fn subscribe(&self) -> broadcast::Receiver<Snapshot> {
+        self.shared.subscribe()
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.shared.interval()
+    }
+
+    pub fn set_interval(&self, interval: Duration) {
+        let interval = interval.max(MIN_INTERVAL);
+        self.shared.interval_ms.store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    // Stop sampling; subscribers and consumer threads finish once they have
+    // drained the samples already published
+    pub fn stop(&self) {
+        self.shared.running.store(false, Ordering::Relaxed);
+        self.shared.close();
+    }
+
+    // Run `consume` on its own thread for every published sample. Blocking
+    // work such as database writes belongs here rather than in async code.
+    pub fn spawn_consumer<F>(&self, name: &str, mut consume: F) -> io::Result<JoinHandle<()>>
+    where
+        F: FnMut(&DetailedMetrics) + Send + 'static,
+    {
+        let mut updates = self.subscribe();
+        let thread_name = name.to_string();
+        thread::Builder::new().name(name.to_string()).spawn(move || loop {
+            match updates.blocking_recv() {
+                Ok(snapshot) => consume(&snapshot),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Consumer {} fell behind, skipped {} samples", thread_name, skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use sysinfo::System;
+    use crate::collector::Collector;
+    use crate::error::MonitorError;
+
+    struct TickCollector(f64);
+
+    impl Collector for TickCollector {
+        fn name(&self) -> &str {
+            "tick"
+        }
+
+        fn collect(&mut self, _sys: &mut System, metrics: &mut DetailedMetrics) -> Result<(), MonitorError> {
+            self.0 += 1.0;
+            metrics.gauges.insert("tick".to_string(), self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_snapshots_reach_consumers() {
+        let mut registry = CollectorRegistry::new();
+        registry.register(Box::new(TickCollector(0.0)));
+        let handle = Sampler::new(registry, MIN_INTERVAL).spawn().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        handle.spawn_consumer("test-consumer", move |m| {
+            let _ = tx.send(m.gauges["tick"]);
+        }).unwrap();
+
+        let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(second > first);
+        assert!(handle.latest().is_some());
+        handle.stop();
+    }
+
+    #[test]
+    fn test_consumers_exit_on_stop() {
+        let handle = Sampler::new(CollectorRegistry::new(), MIN_INTERVAL).spawn().unwrap();
+        let consumer = handle.spawn_consumer("test-consumer", |_| {}).unwrap();
+        handle.stop();
+        consumer.join().unwrap();
+        assert!(handle.subscribe().blocking_recv().is_err());
+    }
+}
@@ -0,0 +1,331 @@
+mod query;
+mod retention;
+mod schema;
+
+pub use self::query::{
+    downsample, percentile, summarize, Aggregation, Metric, Series, SeriesPoint, SeriesQuery, StoredEvent, Summary,
+};
+pub use self::retention::{spawn_compactor, CompactionStats};
+pub use self::schema::SCHEMA_VERSION;
+
+use std::io;
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::error;
+use rusqlite::{params, Connection, OpenFlags, Result, Transaction};
+use crate::config::StorageConfig;
+use crate::monitor::DetailedMetrics;
+use crate::sampler::SamplerHandle;
+
+pub struct MetricsStorage {
+    conn: Connection,
+    max_processes: usize,
+}
+
+impl MetricsStorage {
+    pub fn new() -> Result<Self> {
+        Self::from_config(&StorageConfig::default())
+    }
+
+    pub fn from_config(config: &StorageConfig) -> Result<Self> {
+        let mut storage = Self::open(&config.path)?;
+        storage.max_processes = config.max_processes;
+        Ok(storage)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        // WAL lets readers (web queries, exports) run alongside the recorder
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    // For readers next to a running recorder: never creates or migrates
+    // the database, that is left to the writer
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        Ok(Self {
+            conn,
+            max_processes: StorageConfig::default().max_processes,
+        })
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        schema::migrate(&mut conn)?;
+        Ok(Self {
+            conn,
+            max_processes: StorageConfig::default().max_processes,
+        })
+    }
+
+    pub fn schema_version(&self) -> Result<u32> {
+        schema::version(&self.conn)
+    }
+
+    pub fn store_metrics(&mut self, metrics: &DetailedMetrics) -> Result<()> {
+        self.store_batch(std::slice::from_ref(metrics))
+    }
+
+    // All samples and their child rows are written in one transaction
+    pub fn store_batch(&mut self, batch: &[DetailedMetrics]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for metrics in batch {
+            insert_sample(&tx, metrics, self.max_processes)?;
+        }
+        tx.commit()
+    }
+
+    // Record every sample the sampler publishes, `batch_size` at a time.
+    // A partial batch is written when the sampler stops.
+    pub fn spawn_recorder(self, sampler: &SamplerHandle, batch_size: usize) -> io::Result<JoinHandle<()>> {
+        let batch_size = batch_size.max(1);
+        let mut recorder = Recorder { storage: self, pending: Vec::with_capacity(batch_size) };
+        sampler.spawn_consumer("storage", move |metrics| {
+            recorder.pending.push(metrics.clone());
+            if recorder.pending.len() >= batch_size {
+                recorder.flush();
+            }
+        })
+    }
+}
+
+// Samples waiting to be written; flushed when dropped with the consumer
+struct Recorder {
+    storage: MetricsStorage,
+    pending: Vec<DetailedMetrics>,
+}
+
+impl Recorder {
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        if let Err(e) = self.storage.store_batch(&self.pending) {
+            error!("Failed to store {} samples: {}", self.pending.len(), e);
+        }
+        self.pending.clear();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+pub fn timestamp_ms(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+fn insert_sample(tx: &Transaction, metrics: &DetailedMetrics, max_processes: usize) -> Result<()> {
+    let basic = &metrics.basic;
+    tx.prepare_cached(
+        "INSERT INTO samples (timestamp_ms, cpu_usage, total_memory, used_memory, available_memory,
+            load_one, load_five, load_fifteen, rx_bytes, tx_bytes, connections)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+    )?.execute(params![
+        timestamp_ms(metrics.timestamp),
+        basic.cpu_usage as f64,
+        basic.total_memory as i64,
+        basic.used_memory as i64,
+        basic.available_memory as i64,
+        basic.load_average.one,
+        basic.load_average.five,
+        basic.load_average.fifteen,
+        metrics.network.rx_bytes as i64,
+        metrics.network.tx_bytes as i64,
+        metrics.network.connections as i64,
+    ])?;
+    let sample_id = tx.last_insert_rowid();
+
+    let mut stmt = tx.prepare_cached(
+        "INSERT OR REPLACE INTO disk_samples (sample_id, name, mount_point, total_space, available_space,
+            read_bytes, write_bytes, read_bytes_per_sec, write_bytes_per_sec)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for disk in &metrics.disks {
+        stmt.execute(params![
+            sample_id,
+            disk.name,
+            disk.mount_point,
+            disk.total_space as i64,
+            disk.available_space as i64,
+            disk.read_bytes as i64,
+            disk.write_bytes as i64,
+            disk.read_bytes_per_sec,
+            disk.write_bytes_per_sec,
+        ])?;
+    }
+
+    let mut stmt = tx.prepare_cached("INSERT INTO temperature_samples VALUES (?1, ?2, ?3)")?;
+    for temp in &metrics.temperatures {
+        stmt.execute(params![sample_id, temp.label, temp.value as f64])?;
+    }
+
+    // Only the largest processes are kept, a full process list per
+    // sample would dwarf everything else in the database
+    let mut processes: Vec<_> = metrics.processes.iter().collect();
+    processes.sort_by_key(|p| std::cmp::Reverse(p.memory));
+    let mut stmt = tx.prepare_cached("INSERT OR REPLACE INTO process_samples VALUES (?1, ?2, ?3, ?4, ?5)")?;
+    for process in processes.into_iter().take(max_processes) {
+        stmt.execute(params![
+            sample_id,
+            process.pid,
+            process.name,
+            process.cpu_usage as f64,
+            process.memory as i64,
+        ])?;
+    }
+
+    let mut stmt = tx.prepare_cached("INSERT INTO gauge_samples VALUES (?1, ?2, ?3)")?;
+    for (name, value) in &metrics.gauges {
+        stmt.execute(params![sample_id, name, value])?;
+    }
+
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO events (timestamp_ms, kind, cgroup, pid, process, memory, count, message)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for event in &metrics.events {
+        stmt.execute(params![
+            timestamp_ms(event.timestamp),
+            event.kind.to_string(),
+            event.cgroup,
+            event.pid,
+            event.process,
+            event.memory.map(|m| m as i64),
+            event.count as i64,
+            event.message,
+        ])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::collector::CollectorRegistry;
+    use crate::monitor::{DiskMetrics, MemoryEvent, MemoryEventKind, ProcessMetrics, Temperature};
+    use crate::sampler::Sampler;
+
+    fn count(storage: &MetricsStorage, table: &str) -> i64 {
+        storage.conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn sample(timestamp_ms: u64) -> DetailedMetrics {
+        let mut metrics = DetailedMetrics {
+            timestamp: UNIX_EPOCH + Duration::from_millis(timestamp_ms),
+            ..DetailedMetrics::default()
+        };
+        metrics.basic.cpu_usage = 12.5;
+        let disk = DiskMetrics {
+            name: "/dev/sda1".to_string(),
+            mount_point: "/".to_string(),
+            file_system: "ext4".to_string(),
+            total_space: 100,
+            available_space: 50,
+            read_bytes: 1,
+            write_bytes: 2,
+            read_bytes_per_sec: 0.5,
+            write_bytes_per_sec: 1.0,
+        };
+        // The same device bind-mounted elsewhere
+        metrics.disks.push(DiskMetrics { mount_point: "/srv".to_string(), ..disk.clone() });
+        metrics.disks.push(disk);
+        metrics.temperatures.push(Temperature { label: "cpu".to_string(), value: 40.0 });
+        metrics.processes = (1..=3).map(|pid| ProcessMetrics {
+            pid,
+            name: format!("p{}", pid),
+            cpu_usage: 0.0,
+            memory: pid as u64 * 10,
+            ..ProcessMetrics::default()
+        }).collect();
+        metrics.gauges.insert("queue_depth".to_string(), 7.0);
+        metrics.events.push(MemoryEvent {
+            kind: MemoryEventKind::OomKill,
+            timestamp: metrics.timestamp,
+            cgroup: None,
+            pid: Some(3),
+            process: Some("p3".to_string()),
+            memory: Some(30),
+            count: 1,
+            message: "OOM killer killed process 3 (p3)".to_string(),
+        });
+        metrics
+    }
+
+    #[test]
+    fn test_batch_insert_and_millisecond_timestamps() {
+        let mut storage = MetricsStorage::open_in_memory().unwrap();
+        storage.max_processes = 2;
+        // Same second, different milliseconds: both samples survive
+        storage.store_batch(&[sample(1_000_100), sample(1_000_900)]).unwrap();
+
+        assert_eq!(count(&storage, "samples"), 2);
+        assert_eq!(count(&storage, "disk_samples"), 4);
+        assert_eq!(count(&storage, "temperature_samples"), 2);
+        assert_eq!(count(&storage, "process_samples"), 4);
+        assert_eq!(count(&storage, "gauge_samples"), 2);
+        let events = storage.events(1_000_000, 1_000_500).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].kind.as_str(), events[0].pid), ("oom_kill", Some(3)));
+
+        let cpu: f64 = storage.conn
+            .query_row("SELECT cpu_usage FROM samples WHERE timestamp_ms = 1000900", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(cpu, 12.5);
+
+        storage.conn.execute("DELETE FROM samples", []).unwrap();
+        assert_eq!(count(&storage, "process_samples"), 0);
+        assert_eq!(count(&storage, "events"), 2);
+    }
+
+    #[test]
+    fn test_migrates_legacy_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.db");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(
+                "CREATE TABLE metrics (timestamp INTEGER PRIMARY KEY, cpu_usage REAL,
+                    memory_used INTEGER, memory_total INTEGER)",
+                [],
+            ).unwrap();
+            conn.execute("INSERT INTO metrics VALUES (1700000000, 2550, 512, 1024)", []).unwrap();
+        }
+
+        let storage = MetricsStorage::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), SCHEMA_VERSION);
+        let (ts, cpu): (i64, f64) = storage.conn
+            .query_row("SELECT timestamp_ms, cpu_usage FROM samples", [], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap();
+        assert_eq!(ts, 1_700_000_000_000);
+        assert_eq!(cpu, 25.5);
+        drop(storage);
+
+        // Reopening an up-to-date database is a no-op
+        assert_eq!(MetricsStorage::open(&path).unwrap().schema_version().unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_recorder_flushes_partial_batch_on_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.db");
+        let sampler = Sampler::new(CollectorRegistry::new(), Duration::from_millis(100)).spawn().unwrap();
+        let recorder = MetricsStorage::open(&path).unwrap().spawn_recorder(&sampler, 1000).unwrap();
+
+        std::thread::sleep(Duration::from_millis(300));
+        sampler.stop();
+        recorder.join().unwrap();
+        assert!(count(&MetricsStorage::open(&path).unwrap(), "samples") > 0);
+    }
+}
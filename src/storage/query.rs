@@ -0,0 +1,519 @@
+use std::fmt;
+use std::str::FromStr;
+use rusqlite::{params, Result};
+use serde::Serialize;
+use super::MetricsStorage;
+
+// A single numeric series that can be read back from the database.
+// The string form (`Display`/`FromStr`) is what the web API and CLI use,
+// e.g. `cpu_usage`, `memory.used` or `disk.available_space:/dev/sda1`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Metric {
+    CpuUsage,
+    MemoryUsed,
+    MemoryAvailable,
+    MemoryUsagePercent,
+    LoadOne,
+    LoadFive,
+    LoadFifteen,
+    NetworkRx,
+    NetworkTx,
+    DiskAvailable(String),
+    DiskReadRate(String),
+    DiskWriteRate(String),
+    Temperature(String),
+    // Summed over every process with that name
+    ProcessMemory(String),
+    ProcessCpu(String),
+    Gauge(String),
+}
+
+impl Metric {
+    pub const SAMPLE_METRICS: [Metric; 9] = [
+        Metric::CpuUsage,
+        Metric::MemoryUsed,
+        Metric::MemoryAvailable,
+        Metric::MemoryUsagePercent,
+        Metric::LoadOne,
+        Metric::LoadFive,
+        Metric::LoadFifteen,
+        Metric::NetworkRx,
+        Metric::NetworkTx,
+    ];
+
+    // SQL returning (timestamp_ms, value) rows for ?1 <= timestamp_ms < ?2;
+    // metrics with an argument bind it as ?3
+    fn sql(&self) -> String {
+        let sample_column = |column: &str| {
+            format!(
+                "SELECT timestamp_ms, {column} FROM samples
+                 WHERE timestamp_ms >= ?1 AND timestamp_ms < ?2 AND {column} IS NOT NULL
+                 ORDER BY timestamp_ms"
+            )
+        };
+        let child_column = |table: &str, key: &str, value: &str| {
+            format!(
+                "SELECT s.timestamp_ms, {value} FROM {table} c JOIN samples s ON s.id = c.sample_id
+                 WHERE s.timestamp_ms >= ?1 AND s.timestamp_ms < ?2 AND c.{key} = ?3
+                 GROUP BY s.id ORDER BY s.timestamp_ms"
+            )
+        };
+        match self {
+            Metric::CpuUsage => sample_column("cpu_usage"),
+            Metric::MemoryUsed => sample_column("used_memory"),
+            Metric::MemoryAvailable => sample_column("available_memory"),
+            Metric::MemoryUsagePercent => "SELECT timestamp_ms, 100.0 * used_memory / total_memory FROM samples
+                 WHERE timestamp_ms >= ?1 AND timestamp_ms < ?2 AND total_memory > 0
+                 ORDER BY timestamp_ms".to_string(),
+            Metric::LoadOne => sample_column("load_one"),
+            Metric::LoadFive => sample_column("load_five"),
+            Metric::LoadFifteen => sample_column("load_fifteen"),
+            Metric::NetworkRx => sample_column("rx_bytes"),
+            Metric::NetworkTx => sample_column("tx_bytes"),
+            Metric::DiskAvailable(_) => child_column("disk_samples", "name", "MAX(c.available_space)"),
+            Metric::DiskReadRate(_) => child_column("disk_samples", "name", "MAX(c.read_bytes_per_sec)"),
+            Metric::DiskWriteRate(_) => child_column("disk_samples", "name", "MAX(c.write_bytes_per_sec)"),
+            Metric::Temperature(_) => child_column("temperature_samples", "label", "MAX(c.value)"),
+            Metric::ProcessMemory(_) => child_column("process_samples", "name", "SUM(c.memory)"),
+            Metric::ProcessCpu(_) => child_column("process_samples", "name", "SUM(c.cpu_usage)"),
+            Metric::Gauge(_) => child_column("gauge_samples", "name", "MAX(c.value)"),
+        }
+    }
+
+    fn argument(&self) -> Option<&str> {
+        match self {
+            Metric::DiskAvailable(arg)
+            | Metric::DiskReadRate(arg)
+            | Metric::DiskWriteRate(arg)
+            | Metric::Temperature(arg)
+            | Metric::ProcessMemory(arg)
+            | Metric::ProcessCpu(arg)
+            | Metric::Gauge(arg) => Some(arg),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let family = match self {
+            Metric::CpuUsage => "cpu_usage",
+            Metric::MemoryUsed => "memory.used",
+            Metric::MemoryAvailable => "memory.available",
+            Metric::MemoryUsagePercent => "memory.usage_percent",
+            Metric::LoadOne => "load.one",
+            Metric::LoadFive => "load.five",
+            Metric::LoadFifteen => "load.fifteen",
+            Metric::NetworkRx => "network.rx_bytes",
+            Metric::NetworkTx => "network.tx_bytes",
+            Metric::DiskAvailable(_) => "disk.available_space",
+            Metric::DiskReadRate(_) => "disk.read_bytes_per_sec",
+            Metric::DiskWriteRate(_) => "disk.write_bytes_per_sec",
+            Metric::Temperature(_) => "temperature",
+            Metric::ProcessMemory(_) => "process.memory",
+            Metric::ProcessCpu(_) => "process.cpu_usage",
+            Metric::Gauge(_) => "gauge",
+        };
+        match self.argument() {
+            Some(arg) => write!(f, "{}:{}", family, arg),
+            None => f.write_str(family),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (family, arg) = match s.split_once(':') {
+            Some((family, arg)) if !arg.is_empty() => (family, Some(arg.to_string())),
+            Some(_) => return Err(format!("Metric {} is missing its argument", s)),
+            None => (s, None),
+        };
+        let metric = match (family, arg) {
+            ("cpu_usage", None) => Metric::CpuUsage,
+            ("memory.used", None) => Metric::MemoryUsed,
+            ("memory.available", None) => Metric::MemoryAvailable,
+            ("memory.usage_percent", None) => Metric::MemoryUsagePercent,
+            ("load.one", None) => Metric::LoadOne,
+            ("load.five", None) => Metric::LoadFive,
+            ("load.fifteen", None) => Metric::LoadFifteen,
+            ("network.rx_bytes", None) => Metric::NetworkRx,
+            ("network.tx_bytes", None) => Metric::NetworkTx,
+            ("disk.available_space", Some(arg)) => Metric::DiskAvailable(arg),
+            ("disk.read_bytes_per_sec", Some(arg)) => Metric::DiskReadRate(arg),
+            ("disk.write_bytes_per_sec", Some(arg)) => Metric::DiskWriteRate(arg),
+            ("temperature", Some(arg)) => Metric::Temperature(arg),
+            ("process.memory", Some(arg)) => Metric::ProcessMemory(arg),
+            ("process.cpu_usage", Some(arg)) => Metric::ProcessCpu(arg),
+            ("gauge", Some(arg)) => Metric::Gauge(arg),
+            _ => return Err(format!("Unknown metric: {}", s)),
+        };
+        Ok(metric)
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    Avg,
+    Min,
+    Max,
+    P95,
+}
+
+impl Aggregation {
+    // `values` must not be empty
+    pub fn apply(self, values: &mut [f64]) -> f64 {
+        match self {
+            Aggregation::Avg => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregation::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregation::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::P95 => percentile(values, 95.0),
+        }
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "avg" | "mean" => Ok(Aggregation::Avg),
+            "min" => Ok(Aggregation::Min),
+            "max" => Ok(Aggregation::Max),
+            "p95" => Ok(Aggregation::P95),
+            _ => Err(format!("Unknown aggregation: {}", s)),
+        }
+    }
+}
+
+// Nearest-rank percentile
+pub fn percentile(values: &mut [f64], pct: f64) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((pct / 100.0) * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+#[derive(Debug, Clone)]
+pub struct SeriesQuery {
+    pub metric: Metric,
+    // Half-open range [start_ms, end_ms)
+    pub start_ms: i64,
+    pub end_ms: i64,
+    // Zero returns the raw samples without aggregation
+    pub bucket_secs: u64,
+    pub aggregation: Aggregation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeriesPoint {
+    // Start of the bucket (or the sample time for raw series)
+    pub timestamp_ms: i64,
+    pub value: f64,
+    // Number of raw samples folded into this point
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub metric: Metric,
+    pub aggregation: Aggregation,
+    pub bucket_secs: u64,
+    pub points: Vec<SeriesPoint>,
+}
+
+impl Series {
+    // (seconds since the first point, value) pairs for tui's Chart widget
+    pub fn chart_data(&self) -> Vec<(f64, f64)> {
+        let origin = self.points.first().map(|p| p.timestamp_ms).unwrap_or(0);
+        self.points
+            .iter()
+            .map(|p| ((p.timestamp_ms - origin) as f64 / 1000.0, p.value))
+            .collect()
+    }
+}
+
+// Aggregates of one time bucket. A raw sample is a summary of itself;
+// rollup rows are summaries of many samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub timestamp_ms: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub p95: f64,
+    pub samples: usize,
+}
+
+impl Summary {
+    pub fn of_sample(timestamp_ms: i64, value: f64) -> Self {
+        Self { timestamp_ms, avg: value, min: value, max: value, p95: value, samples: 1 }
+    }
+
+    // Combine summaries into one bucket. Percentiles are exact while every
+    // part is a raw sample; once rollups are involved the largest p95 of the
+    // parts is used as a (conservative) approximation.
+    pub fn merge(timestamp_ms: i64, parts: &[Summary]) -> Self {
+        let samples: usize = parts.iter().map(|p| p.samples).sum();
+        let p95 = if parts.iter().all(|p| p.samples == 1) {
+            let mut values: Vec<f64> = parts.iter().map(|p| p.avg).collect();
+            percentile(&mut values, 95.0)
+        } else {
+            parts.iter().map(|p| p.p95).fold(f64::NEG_INFINITY, f64::max)
+        };
+        Self {
+            timestamp_ms,
+            avg: parts.iter().map(|p| p.avg * p.samples as f64).sum::<f64>() / samples.max(1) as f64,
+            min: parts.iter().map(|p| p.min).fold(f64::INFINITY, f64::min),
+            max: parts.iter().map(|p| p.max).fold(f64::NEG_INFINITY, f64::max),
+            p95,
+            samples,
+        }
+    }
+
+    pub fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Avg => self.avg,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::P95 => self.p95,
+        }
+    }
+}
+
+// Merge time-ordered summaries that fall into the same bucket
+pub fn summarize(parts: &[Summary], bucket_secs: u64) -> Vec<Summary> {
+    // Anything too large to express in milliseconds is one bucket
+    let bucket_ms = i64::try_from(bucket_secs).ok().and_then(|secs| secs.checked_mul(1000)).unwrap_or(i64::MAX).max(1);
+    let bucket_of = |timestamp_ms: i64| timestamp_ms - timestamp_ms.rem_euclid(bucket_ms);
+    parts
+        .chunk_by(|a, b| bucket_of(a.timestamp_ms) == bucket_of(b.timestamp_ms))
+        .map(|chunk| Summary::merge(bucket_of(chunk[0].timestamp_ms), chunk))
+        .collect()
+}
+
+fn to_points(parts: &[Summary], bucket_secs: u64, aggregation: Aggregation) -> Vec<SeriesPoint> {
+    let buckets;
+    let parts = if bucket_secs == 0 {
+        parts
+    } else {
+        buckets = summarize(parts, bucket_secs);
+        &buckets
+    };
+    parts
+        .iter()
+        .map(|s| SeriesPoint { timestamp_ms: s.timestamp_ms, value: s.value(aggregation), samples: s.samples })
+        .collect()
+}
+
+// Fold time-ordered raw points into fixed-size buckets
+pub fn downsample(raw: &[(i64, f64)], bucket_secs: u64, aggregation: Aggregation) -> Vec<SeriesPoint> {
+    let parts: Vec<_> = raw.iter().map(|&(ts, value)| Summary::of_sample(ts, value)).collect();
+    to_points(&parts, bucket_secs, aggregation)
+}
+
+// A row of the events table
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredEvent {
+    pub timestamp_ms: i64,
+    pub kind: String,
+    pub cgroup: Option<String>,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+    pub memory: Option<i64>,
+    pub count: i64,
+    pub message: String,
+}
+
+impl MetricsStorage {
+    // Raw samples where they still exist, rollups for older parts of the range
+    pub fn query_series(&self, query: &SeriesQuery) -> Result<Series> {
+        let raw = self.raw_points(&query.metric, query.start_ms, query.end_ms)?;
+        let raw_from = raw.first().map_or(query.end_ms, |&(ts, _)| ts);
+
+        let mut parts = self.rollup_summaries(&query.metric, query.start_ms, raw_from)?;
+        parts.extend(raw.iter().map(|&(ts, value)| Summary::of_sample(ts, value)));
+
+        Ok(Series {
+            metric: query.metric.clone(),
+            aggregation: query.aggregation,
+            bucket_secs: query.bucket_secs,
+            points: to_points(&parts, query.bucket_secs, query.aggregation),
+        })
+    }
+
+    // Rollups covering [start_ms, end_ms), finest tier first; coarser tiers
+    // only fill in the time before the finer tier's oldest bucket.
+    pub fn rollup_summaries(&self, metric: &Metric, start_ms: i64, end_ms: i64) -> Result<Vec<Summary>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT DISTINCT resolution_secs FROM rollups ORDER BY resolution_secs",
+        )?;
+        let resolutions = stmt
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT bucket_start_ms, avg, min, max, p95, samples FROM rollups
+             WHERE resolution_secs = ?1 AND metric = ?2
+               AND bucket_start_ms >= ?3 AND bucket_start_ms + ?1 * 1000 <= ?4
+             ORDER BY bucket_start_ms",
+        )?;
+        let metric = metric.to_string();
+        let mut covered_from = end_ms;
+        let mut summaries = Vec::new();
+        for resolution in resolutions {
+            let aligned_start = start_ms - start_ms.rem_euclid(resolution * 1000);
+            let rows = stmt
+                .query_map(params![resolution, metric, aligned_start, covered_from], |row| {
+                    Ok(Summary {
+                        timestamp_ms: row.get(0)?,
+                        avg: row.get(1)?,
+                        min: row.get(2)?,
+                        max: row.get(3)?,
+                        p95: row.get(4)?,
+                        samples: row.get::<_, i64>(5)? as usize,
+                    })
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(first) = rows.first() {
+                covered_from = first.timestamp_ms;
+            }
+            summaries.extend(rows);
+        }
+        summaries.sort_by_key(|s| s.timestamp_ms);
+        Ok(summaries)
+    }
+
+    pub fn raw_points(&self, metric: &Metric, start_ms: i64, end_ms: i64) -> Result<Vec<(i64, f64)>> {
+        let mut stmt = self.conn.prepare_cached(&metric.sql())?;
+        let map = |row: &rusqlite::Row| Ok((row.get(0)?, row.get(1)?));
+        let rows = match metric.argument() {
+            Some(arg) => stmt.query_map(params![start_ms, end_ms, arg], map)?,
+            None => stmt.query_map(params![start_ms, end_ms], map)?,
+        };
+        rows.collect()
+    }
+
+    // Events in [start_ms, end_ms), oldest first
+    pub fn events(&self, start_ms: i64, end_ms: i64) -> Result<Vec<StoredEvent>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT timestamp_ms, kind, cgroup, pid, process, memory, count, message FROM events
+             WHERE timestamp_ms >= ?1 AND timestamp_ms < ?2 ORDER BY timestamp_ms, id",
+        )?;
+        let rows = stmt.query_map(params![start_ms, end_ms], |row| {
+            Ok(StoredEvent {
+                timestamp_ms: row.get(0)?,
+                kind: row.get(1)?,
+                cgroup: row.get(2)?,
+                pid: row.get(3)?,
+                process: row.get(4)?,
+                memory: row.get(5)?,
+                count: row.get(6)?,
+                message: row.get(7)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    // Every metric with at least one sample in the range
+    pub fn list_metrics(&self, start_ms: i64, end_ms: i64) -> Result<Vec<Metric>> {
+        let mut metrics = Vec::new();
+        let has_samples: bool = self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM samples WHERE timestamp_ms >= ?1 AND timestamp_ms < ?2)",
+            params![start_ms, end_ms],
+            |row| row.get(0),
+        )?;
+        if has_samples {
+            metrics.extend(Metric::SAMPLE_METRICS.iter().cloned());
+        }
+
+        let distinct = |table: &str, key: &str| -> Result<Vec<String>> {
+            let sql = format!(
+                "SELECT DISTINCT c.{key} FROM {table} c JOIN samples s ON s.id = c.sample_id
+                 WHERE s.timestamp_ms >= ?1 AND s.timestamp_ms < ?2 ORDER BY c.{key}"
+            );
+            let mut stmt = self.conn.prepare(&sql)?;
+            let names = stmt.query_map(params![start_ms, end_ms], |row| row.get(0))?;
+            names.collect()
+        };
+        for name in distinct("disk_samples", "name")? {
+            metrics.push(Metric::DiskAvailable(name.clone()));
+            metrics.push(Metric::DiskReadRate(name.clone()));
+            metrics.push(Metric::DiskWriteRate(name));
+        }
+        metrics.extend(distinct("temperature_samples", "label")?.into_iter().map(Metric::Temperature));
+        for name in distinct("process_samples", "name")? {
+            metrics.push(Metric::ProcessMemory(name.clone()));
+            metrics.push(Metric::ProcessCpu(name));
+        }
+        metrics.extend(distinct("gauge_samples", "name")?.into_iter().map(Metric::Gauge));
+        Ok(metrics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::monitor::DetailedMetrics;
+
+    #[test]
+    fn test_metric_names_round_trip() {
+        for name in ["cpu_usage", "memory.usage_percent", "disk.available_space:/dev/sda1", "gauge:a:b"] {
+            assert_eq!(name.parse::<Metric>().unwrap().to_string(), name);
+        }
+        assert!("disk.available_space".parse::<Metric>().is_err());
+        assert!("cpu_usage:x".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn test_downsampled_range_query() {
+        let mut storage = MetricsStorage::open_in_memory().unwrap();
+        // Two 10-second buckets with cpu values 1..=10 and 11..=20
+        let batch: Vec<_> = (0..20u64).map(|i| {
+            let mut m = DetailedMetrics {
+                timestamp: UNIX_EPOCH + Duration::from_millis(100_000 + i * 1000),
+                ..DetailedMetrics::default()
+            };
+            m.basic.cpu_usage = (i + 1) as f32;
+            m.gauges.insert("queue".to_string(), i as f64);
+            m
+        }).collect();
+        storage.store_batch(&batch).unwrap();
+
+        let mut query = SeriesQuery {
+            metric: Metric::CpuUsage,
+            start_ms: 100_000,
+            end_ms: 120_000,
+            bucket_secs: 10,
+            aggregation: Aggregation::Avg,
+        };
+        let series = storage.query_series(&query).unwrap();
+        let values: Vec<_> = series.points.iter().map(|p| (p.timestamp_ms, p.value, p.samples)).collect();
+        assert_eq!(values, vec![(100_000, 5.5, 10), (110_000, 15.5, 10)]);
+
+        query.aggregation = Aggregation::P95;
+        assert_eq!(storage.query_series(&query).unwrap().points[1].value, 20.0);
+        query.aggregation = Aggregation::Min;
+        query.end_ms = 105_000;
+        assert_eq!(storage.query_series(&query).unwrap().points[0].value, 1.0);
+
+        query.metric = Metric::Gauge("queue".to_string());
+        query.bucket_secs = 0;
+        assert_eq!(storage.query_series(&query).unwrap().points.len(), 5);
+        // Too large for milliseconds: a single bucket rather than an overflow
+        query.bucket_secs = u64::MAX;
+        assert_eq!(storage.query_series(&query).unwrap().points.len(), 1);
+
+        let listed = storage.list_metrics(0, i64::MAX).unwrap();
+        assert!(listed.contains(&Metric::Gauge("queue".to_string())));
+        assert!(listed.contains(&Metric::LoadFifteen));
+    }
+}
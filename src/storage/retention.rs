@@ -0,0 +1,305 @@
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use log::{error, info};
+use rusqlite::{params, OptionalExtension, Result};
+use crate::config::{RetentionConfig, StorageConfig};
+use super::query::{summarize, Summary};
+use super::{timestamp_ms, MetricsStorage};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub rollups_written: usize,
+    pub samples_pruned: usize,
+    pub rollups_pruned: usize,
+    pub events_pruned: usize,
+}
+
+impl MetricsStorage {
+    // Roll complete buckets up into every tier, then drop data that is past
+    // its retention. Nothing is pruned before the next tier has absorbed it.
+    // Raw samples are only rolled up to `settled_ms`: a recorder may still
+    // hold later ones in its pending batch.
+    pub fn compact(&mut self, retention: &RetentionConfig, now_ms: i64, settled_ms: i64) -> Result<CompactionStats> {
+        let mut tiers = retention.tiers.clone();
+        tiers.retain(|t| t.resolution_secs > 0);
+        tiers.sort_by_key(|t| t.resolution_secs);
+
+        let mut stats = CompactionStats::default();
+        let mut source_until = settled_ms.min(now_ms);
+        let mut source: Option<u64> = None;
+        let mut progress = Vec::with_capacity(tiers.len());
+
+        for tier in &tiers {
+            let resolution_ms = tier.resolution_secs as i64 * 1000;
+            let until = source_until - source_until.rem_euclid(resolution_ms);
+            let from = match self.rolled_until(tier.resolution_secs)? {
+                Some(from) => Some(from),
+                None => self.oldest_source(source)?.map(|ts| ts - ts.rem_euclid(resolution_ms)),
+            };
+
+            let rolled_until = match from {
+                Some(from) if from < until => {
+                    stats.rollups_written += self.roll_up(source, tier.resolution_secs, from, until)?;
+                    until
+                }
+                Some(from) => from,
+                None => until,
+            };
+            progress.push(rolled_until);
+            source_until = rolled_until;
+            source = Some(tier.resolution_secs);
+        }
+
+        let tx = self.conn.transaction()?;
+        let mut raw_cutoff = now_ms - retention.raw_secs as i64 * 1000;
+        if let Some(&first) = progress.first() {
+            raw_cutoff = raw_cutoff.min(first);
+        }
+        stats.samples_pruned = tx.execute("DELETE FROM samples WHERE timestamp_ms < ?1", [raw_cutoff])?;
+
+        for (i, tier) in tiers.iter().enumerate() {
+            let mut cutoff = now_ms - tier.keep_secs as i64 * 1000;
+            if let Some(&next) = progress.get(i + 1) {
+                cutoff = cutoff.min(next);
+            }
+            stats.rollups_pruned += tx.execute(
+                "DELETE FROM rollups WHERE resolution_secs = ?1 AND bucket_start_ms < ?2",
+                params![tier.resolution_secs as i64, cutoff],
+            )?;
+        }
+
+        // Events are few; keep them as long as any history of their time exists
+        let keep_secs = tiers.iter().map(|t| t.keep_secs).max().unwrap_or(0).max(retention.raw_secs);
+        stats.events_pruned = tx.execute(
+            "DELETE FROM events WHERE timestamp_ms < ?1",
+            [now_ms - keep_secs as i64 * 1000],
+        )?;
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")
+    }
+
+    fn rolled_until(&self, resolution_secs: u64) -> Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT rolled_until_ms FROM rollup_progress WHERE resolution_secs = ?1",
+                [resolution_secs as i64],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    // Oldest timestamp in the raw samples (None) or in a rollup tier
+    fn oldest_source(&self, source: Option<u64>) -> Result<Option<i64>> {
+        match source {
+            None => self.conn.query_row("SELECT MIN(timestamp_ms) FROM samples", [], |row| row.get(0)),
+            Some(resolution) => self.conn.query_row(
+                "SELECT MIN(bucket_start_ms) FROM rollups WHERE resolution_secs = ?1",
+                [resolution as i64],
+                |row| row.get(0),
+            ),
+        }
+    }
+
+    fn roll_up(&mut self, source: Option<u64>, resolution_secs: u64, from: i64, until: i64) -> Result<usize> {
+        let mut rollups: Vec<(String, Summary)> = Vec::new();
+        match source {
+            None => {
+                for metric in self.list_metrics(from, until)? {
+                    let raw: Vec<_> = self
+                        .raw_points(&metric, from, until)?
+                        .into_iter()
+                        .map(|(ts, value)| Summary::of_sample(ts, value))
+                        .collect();
+                    let name = metric.to_string();
+                    rollups.extend(summarize(&raw, resolution_secs).into_iter().map(|s| (name.clone(), s)));
+                }
+            }
+            Some(source) => {
+                let mut stmt = self.conn.prepare_cached(
+                    "SELECT metric, bucket_start_ms, avg, min, max, p95, samples FROM rollups
+                     WHERE resolution_secs = ?1 AND bucket_start_ms >= ?2 AND bucket_start_ms < ?3
+                     ORDER BY metric, bucket_start_ms",
+                )?;
+                let rows = stmt
+                    .query_map(params![source as i64, from, until], |row| {
+                        Ok((row.get::<_, String>(0)?, Summary {
+                            timestamp_ms: row.get(1)?,
+                            avg: row.get(2)?,
+                            min: row.get(3)?,
+                            max: row.get(4)?,
+                            p95: row.get(5)?,
+                            samples: row.get::<_, i64>(6)? as usize,
+                        }))
+                    })?
+                    .collect::<Result<Vec<_>>>()?;
+                for chunk in rows.chunk_by(|a, b| a.0 == b.0) {
+                    let parts: Vec<_> = chunk.iter().map(|(_, s)| *s).collect();
+                    let name = &chunk[0].0;
+                    rollups.extend(summarize(&parts, resolution_secs).into_iter().map(|s| (name.clone(), s)));
+                }
+            }
+        }
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO rollups VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for (metric, s) in &rollups {
+                stmt.execute(params![
+                    resolution_secs as i64,
+                    s.timestamp_ms,
+                    metric,
+                    s.avg,
+                    s.min,
+                    s.max,
+                    s.p95,
+                    s.samples as i64,
+                ])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO rollup_progress VALUES (?1, ?2)",
+            params![resolution_secs as i64, until],
+        )?;
+        tx.commit()?;
+        Ok(rollups.len())
+    }
+}
+
+// Run compaction (and VACUUM, if enabled) on a schedule, with a
+// connection of its own so it never blocks the recorder. Samples taken
+// every `sample_interval` may reach the database up to a full batch late.
+pub fn spawn_compactor(config: StorageConfig, sample_interval: Duration) -> io::Result<JoinHandle<()>> {
+    let retention = config.retention.clone();
+    let flush_horizon = sample_interval.saturating_mul(config.batch_size.max(1) as u32 + 1);
+    let interval = Duration::from_secs(retention.compaction_interval_secs.max(1));
+    let vacuum_interval = Duration::from_secs(retention.vacuum_interval_secs);
+
+    thread::Builder::new().name("compactor".to_string()).spawn(move || {
+        let mut storage = match MetricsStorage::from_config(&config) {
+            Ok(storage) => storage,
+            Err(e) => {
+                error!("Compaction disabled, cannot open {}: {}", config.path, e);
+                return;
+            }
+        };
+        let mut last_vacuum = Instant::now();
+
+        loop {
+            thread::sleep(interval);
+            let now = SystemTime::now();
+            let settled = now.checked_sub(flush_horizon).unwrap_or(now);
+            match storage.compact(&retention, timestamp_ms(now), timestamp_ms(settled)) {
+                Ok(stats) => info!(
+                    "Compaction wrote {} rollups, pruned {} samples, {} rollups and {} events",
+                    stats.rollups_written, stats.samples_pruned, stats.rollups_pruned, stats.events_pruned
+                ),
+                Err(e) => error!("Compaction failed: {}", e),
+            }
+
+            if !vacuum_interval.is_zero() && last_vacuum.elapsed() >= vacuum_interval {
+                if let Err(e) = storage.vacuum() {
+                    error!("VACUUM failed: {}", e);
+                }
+                last_vacuum = Instant::now();
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+    use crate::config::RollupTier;
+    use crate::monitor::DetailedMetrics;
+    use crate::storage::{Aggregation, Metric, SeriesQuery};
+
+    const HOUR_MS: i64 = 3_600_000;
+
+    fn count(storage: &MetricsStorage, sql: &str) -> i64 {
+        storage.conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_compaction_keeps_history_queryable() {
+        let mut storage = MetricsStorage::open_in_memory().unwrap();
+        // Three hours of samples every 10s, cpu rising by 1 per hour
+        let batch: Vec<_> = (0..3 * 360).map(|i: i64| {
+            let mut m = DetailedMetrics {
+                timestamp: UNIX_EPOCH + Duration::from_millis((i * 10_000) as u64),
+                ..DetailedMetrics::default()
+            };
+            m.basic.cpu_usage = (i / 360) as f32;
+            m
+        }).collect();
+        storage.store_batch(&batch).unwrap();
+
+        let retention = RetentionConfig {
+            raw_secs: 3600,
+            tiers: vec![
+                RollupTier { resolution_secs: 60, keep_secs: 2 * 3600 },
+                RollupTier { resolution_secs: 3600, keep_secs: 365 * 24 * 3600 },
+            ],
+            compaction_interval_secs: 60,
+            vacuum_interval_secs: 0,
+        };
+        let stats = storage.compact(&retention, 3 * HOUR_MS, 3 * HOUR_MS).unwrap();
+        assert!(stats.rollups_written > 0);
+        assert_eq!(stats.samples_pruned, 2 * 360);
+        assert_eq!(count(&storage, "SELECT MIN(timestamp_ms) FROM samples"), 2 * HOUR_MS);
+        // The first hour of minute rollups is past its retention
+        assert_eq!(
+            count(&storage, "SELECT MIN(bucket_start_ms) FROM rollups WHERE resolution_secs = 60"),
+            HOUR_MS
+        );
+
+        // Compacting again is a no-op
+        let again = storage.compact(&retention, 3 * HOUR_MS, 3 * HOUR_MS).unwrap();
+        assert_eq!(again, CompactionStats::default());
+
+        // Hourly averages stitched from hour rollups, minute rollups and raw samples
+        let series = storage.query_series(&SeriesQuery {
+            metric: Metric::CpuUsage,
+            start_ms: 0,
+            end_ms: 3 * HOUR_MS,
+            bucket_secs: 3600,
+            aggregation: Aggregation::Avg,
+        }).unwrap();
+        let values: Vec<_> = series.points.iter().map(|p| (p.value, p.samples)).collect();
+        assert_eq!(values, vec![(0.0, 360), (1.0, 360), (2.0, 360)]);
+    }
+
+    #[test]
+    fn test_compaction_waits_for_pending_samples() {
+        let mut storage = MetricsStorage::open_in_memory().unwrap();
+        let minute = |m: i64| {
+            let mut metrics = DetailedMetrics {
+                timestamp: UNIX_EPOCH + Duration::from_millis((m * 60_000) as u64),
+                ..DetailedMetrics::default()
+            };
+            metrics.basic.cpu_usage = m as f32;
+            metrics
+        };
+        storage.store_batch(&(0..5).map(minute).collect::<Vec<_>>()).unwrap();
+
+        let retention = RetentionConfig {
+            raw_secs: 3600,
+            tiers: vec![RollupTier { resolution_secs: 60, keep_secs: 3600 }],
+            compaction_interval_secs: 60,
+            vacuum_interval_secs: 0,
+        };
+        // Minutes 5 to 9 are still in the recorder's batch
+        storage.compact(&retention, 10 * 60_000, 5 * 60_000).unwrap();
+        storage.store_batch(&(5..10).map(minute).collect::<Vec<_>>()).unwrap();
+        storage.compact(&retention, 20 * 60_000, 20 * 60_000).unwrap();
+
+        assert_eq!(count(&storage, "SELECT COUNT(*) FROM rollups WHERE metric = 'cpu_usage'"), 10);
+    }
+}
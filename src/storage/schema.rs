@@ -0,0 +1,150 @@
+use rusqlite::{Connection, Result};
+
+// Each entry upgrades the schema by one version; the index + 1 is the
+// version stored in `PRAGMA user_version` once it has been applied.
+// Never edit a released migration, append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // v1: normalized sample tables. Databases created before versioning
+    // only have the four-column `metrics` table keyed by second; its rows
+    // are carried over (cpu_usage was stored as an integer times 100).
+    "
+    CREATE TABLE IF NOT EXISTS metrics (
+        timestamp INTEGER PRIMARY KEY,
+        cpu_usage REAL,
+        memory_used INTEGER,
+        memory_total INTEGER
+    );
+
+    CREATE TABLE samples (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp_ms INTEGER NOT NULL,
+        cpu_usage REAL NOT NULL,
+        total_memory INTEGER NOT NULL,
+        used_memory INTEGER NOT NULL,
+        available_memory INTEGER,
+        load_one REAL,
+        load_five REAL,
+        load_fifteen REAL,
+        rx_bytes INTEGER,
+        tx_bytes INTEGER,
+        connections INTEGER
+    );
+    CREATE INDEX idx_samples_timestamp ON samples (timestamp_ms);
+
+    CREATE TABLE disk_samples (
+        sample_id INTEGER NOT NULL REFERENCES samples (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        total_space INTEGER NOT NULL,
+        available_space INTEGER NOT NULL,
+        read_bytes INTEGER NOT NULL,
+        write_bytes INTEGER NOT NULL,
+        read_bytes_per_sec REAL NOT NULL,
+        write_bytes_per_sec REAL NOT NULL,
+        PRIMARY KEY (sample_id, name)
+    );
+
+    CREATE TABLE temperature_samples (
+        sample_id INTEGER NOT NULL REFERENCES samples (id) ON DELETE CASCADE,
+        label TEXT NOT NULL,
+        value REAL NOT NULL
+    );
+    CREATE INDEX idx_temperature_samples_sample ON temperature_samples (sample_id);
+
+    CREATE TABLE process_samples (
+        sample_id INTEGER NOT NULL REFERENCES samples (id) ON DELETE CASCADE,
+        pid INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        cpu_usage REAL NOT NULL,
+        memory INTEGER NOT NULL,
+        PRIMARY KEY (sample_id, pid)
+    );
+
+    CREATE TABLE gauge_samples (
+        sample_id INTEGER NOT NULL REFERENCES samples (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        value REAL NOT NULL,
+        PRIMARY KEY (sample_id, name)
+    );
+
+    INSERT INTO samples (timestamp_ms, cpu_usage, total_memory, used_memory)
+        SELECT timestamp * 1000, cpu_usage / 100.0, memory_total, memory_used FROM metrics;
+    DROP TABLE metrics;
+    ",
+    // v2: pre-aggregated rollups for the retention tiers, and how far
+    // each tier has been rolled up
+    "
+    CREATE TABLE rollups (
+        resolution_secs INTEGER NOT NULL,
+        bucket_start_ms INTEGER NOT NULL,
+        metric TEXT NOT NULL,
+        avg REAL NOT NULL,
+        min REAL NOT NULL,
+        max REAL NOT NULL,
+        p95 REAL NOT NULL,
+        samples INTEGER NOT NULL,
+        PRIMARY KEY (resolution_secs, metric, bucket_start_ms)
+    );
+
+    CREATE TABLE rollup_progress (
+        resolution_secs INTEGER PRIMARY KEY,
+        rolled_until_ms INTEGER NOT NULL
+    );
+    ",
+    // v3: OOM kills and cgroup memory limit events. Not tied to a sample,
+    // so they outlive the raw samples.
+    "
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp_ms INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        cgroup TEXT,
+        pid INTEGER,
+        process TEXT,
+        memory INTEGER,
+        count INTEGER NOT NULL,
+        message TEXT NOT NULL
+    );
+    CREATE INDEX idx_events_timestamp ON events (timestamp_ms);
+    ",
+    // v4: disks are keyed by device and mount point, so one device
+    // mounted twice (bind mounts, btrfs subvolumes) keeps both rows
+    "
+    CREATE TABLE disk_samples_v4 (
+        sample_id INTEGER NOT NULL REFERENCES samples (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        mount_point TEXT NOT NULL,
+        total_space INTEGER NOT NULL,
+        available_space INTEGER NOT NULL,
+        read_bytes INTEGER NOT NULL,
+        write_bytes INTEGER NOT NULL,
+        read_bytes_per_sec REAL NOT NULL,
+        write_bytes_per_sec REAL NOT NULL,
+        PRIMARY KEY (sample_id, name, mount_point)
+    );
+    INSERT INTO disk_samples_v4
+        SELECT sample_id, name, '', total_space, available_space, read_bytes, write_bytes,
+            read_bytes_per_sec, write_bytes_per_sec
+        FROM disk_samples;
+    DROP TABLE disk_samples;
+    ALTER TABLE disk_samples_v4 RENAME TO disk_samples;
+    ",
+];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+pub fn version(conn: &Connection) -> Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+// Apply every migration newer than the database, each in its own
+// transaction so a failure leaves the last good version in place.
+pub fn migrate(conn: &mut Connection) -> Result<()> {
+    let current = version(conn)? as usize;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", (index + 1) as u32)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
@@ -1,65 +1,326 @@
-use actix_web::{web, App, HttpServer, HttpResponse, Responder};
+pub mod prometheus;
+
+use actix_web::{web, App, HttpRequest, HttpServer, HttpResponse, Responder};
+use actix_web::http::header::{HeaderValue, AUTHORIZATION};
 use actix_files as fs;
-use std::sync::{Arc, Mutex};
-use sysinfo::{System, SystemExt};
-use crate::monitor::{get_detailed_metrics, DetailedMetrics};
+use log::{error, warn};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use crate::config::{Config, StorageConfig};
+use crate::error::MonitorError;
+use crate::logger::AUDIT_TARGET;
+use crate::process::{build_tree, perform_audited, read_memory_of, sort_tree, ProcessAction, ProcessNode, PROC_ROOT};
+use crate::sampler::{SamplerHandle, Snapshot};
+use crate::storage::{timestamp_ms, Aggregation, Metric, MetricsStorage, SeriesQuery};
+
+const HISTORY_SIZE: usize = 100;
+const DEFAULT_RANGE_MS: i64 = 60 * 60 * 1000;
+const DEFAULT_BUCKET_SECS: u64 = 60;
+// Ten years; larger buckets would overflow millisecond timestamps
+const MAX_BUCKET_SECS: u64 = 10 * 366 * 24 * 60 * 60;
 
 pub struct AppState {
-    system: System,
-    metrics_history: Vec<DetailedMetrics>,
+    sampler: SamplerHandle,
+    metrics_history: Arc<RwLock<VecDeque<Snapshot>>>,
+    storage: StorageConfig,
+    // Required by process actions; None disables them
+    api_token: Option<String>,
+}
+
+impl AppState {
+    // Keeps the last HISTORY_SIZE samples published by the sampler
+    pub fn new(sampler: SamplerHandle, storage: StorageConfig) -> Self {
+        let metrics_history = Arc::new(RwLock::new(VecDeque::with_capacity(HISTORY_SIZE)));
+        let history = metrics_history.clone();
+        let mut updates = sampler.subscribe();
+
+        actix_web::rt::spawn(async move {
+            use tokio::sync::broadcast::error::RecvError;
+            loop {
+                match updates.recv().await {
+                    Ok(snapshot) => {
+                        let mut history = history.write().unwrap_or_else(|e| e.into_inner());
+                        history.push_back(snapshot);
+                        if history.len() > HISTORY_SIZE {
+                            history.pop_front();
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Self { sampler, metrics_history, storage, api_token: None }
+    }
+
+    pub fn with_api_token(mut self, token: Option<String>) -> Self {
+        self.api_token = token.filter(|t| !t.is_empty());
+        self
+    }
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        Self {
-            system: System::new_all(),
-            metrics_history: Vec::new(),
+async fn get_metrics(data: web::Data<AppState>) -> impl Responder {
+    match data.sampler.latest() {
+        Some(metrics) => HttpResponse::Ok().json(&*metrics),
+        None => HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    }
+}
+
+async fn get_history(data: web::Data<AppState>) -> impl Responder {
+    match data.metrics_history.read() {
+        Ok(history) => {
+            let history: Vec<_> = history.iter().map(|m| &**m).collect();
+            HttpResponse::Ok().json(history)
+        }
+        Err(e) => {
+            println!("Failed to lock state: {:?}", e);
+            HttpResponse::InternalServerError().json("Internal server error")
+        }
+    }
+}
+
+async fn get_prometheus_metrics(data: web::Data<AppState>) -> impl Responder {
+    match data.sampler.latest() {
+        Some(metrics) => HttpResponse::Ok()
+            .content_type(prometheus::CONTENT_TYPE)
+            .body(prometheus::render(&metrics)),
+        None => HttpResponse::ServiceUnavailable().body("No metrics collected yet"),
+    }
+}
+
+#[derive(Deserialize)]
+struct TreeParams {
+    // Only the subtree rooted at this process
+    pid: Option<u32>,
+}
+
+// Socket states, listening ports and top peers from the latest sample
+async fn get_connections(data: web::Data<AppState>) -> impl Responder {
+    match data.sampler.latest() {
+        Some(metrics) => HttpResponse::Ok().json(&metrics.sockets),
+        None => HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    }
+}
+
+#[derive(Deserialize)]
+struct CgroupParams {
+    // "container", "pod", "service", "scope", "slice" or "other"
+    kind: Option<String>,
+}
+
+// Per-cgroup usage of the latest sample, largest memory first
+async fn get_cgroups(data: web::Data<AppState>, params: web::Query<CgroupParams>) -> impl Responder {
+    let metrics = match data.sampler.latest() {
+        Some(metrics) => metrics,
+        None => return HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    };
+    let cgroups: Vec<_> = metrics.cgroups.iter()
+        .filter(|cg| params.kind.as_deref().is_none_or(|kind| cg.kind.as_str() == kind))
+        .collect();
+    HttpResponse::Ok().json(cgroups)
+}
+
+// Processes whose memory has been growing steadily, fastest first
+async fn get_leaks(data: web::Data<AppState>) -> impl Responder {
+    match data.sampler.latest() {
+        Some(metrics) => HttpResponse::Ok().json(&metrics.leaks),
+        None => HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    }
+}
+
+// One process of the latest sample with its memory breakdown, read now if
+// it is not among the largest processes the collector covers
+async fn get_process(data: web::Data<AppState>, pid: web::Path<u32>) -> impl Responder {
+    let pid = pid.into_inner();
+    let metrics = match data.sampler.latest() {
+        Some(metrics) => metrics,
+        None => return HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    };
+    let mut process = match metrics.processes.iter().find(|p| p.pid == pid) {
+        Some(process) => process.clone(),
+        None => return HttpResponse::NotFound().json(format!("No process {}", pid)),
+    };
+    if process.memory_detail.is_some() {
+        return HttpResponse::Ok().json(process);
+    }
+    // smaps_rollup walks every mapping of the process, keep it off the workers
+    let result = web::block(move || {
+        process.memory_detail = read_memory_of(std::path::Path::new(PROC_ROOT), pid, process.start_time).ok();
+        process
+    }).await;
+    match result {
+        Ok(process) => HttpResponse::Ok().json(process),
+        Err(e) => {
+            error!("Process task failed: {}", e);
+            HttpResponse::InternalServerError().json("Internal server error")
         }
     }
 }
 
-async fn get_metrics(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
-    let result = data.lock().map_err(|e| {
-        println!("Failed to lock state: {:?}", e);
-        HttpResponse::InternalServerError().json("Internal server error")
-    });
-
-    if let Ok(mut state) = result {
-        state.system.refresh_all();
-        let metrics = get_detailed_metrics(&mut state.system);
-        
-        // Keep only essential logging
-        // println!("Metrics updated: CPU: {}%, Memory: {}%", 
-        //     metrics.basic.cpu_usage,
-        //     (metrics.basic.used_memory as f64 / metrics.basic.total_memory as f64 * 100.0)
-        // );
-        
-        state.metrics_history.push(metrics.clone());
-        if state.metrics_history.len() > 100 {
-            state.metrics_history.remove(0);
+// Process forest of the latest sample, largest subtrees (by memory) first
+async fn get_process_tree(data: web::Data<AppState>, params: web::Query<TreeParams>) -> impl Responder {
+    let metrics = match data.sampler.latest() {
+        Some(metrics) => metrics,
+        None => return HttpResponse::ServiceUnavailable().json("No metrics collected yet"),
+    };
+    let mut tree = build_tree(&metrics.processes);
+    sort_tree(&mut tree, |a, b| b.total_memory.cmp(&a.total_memory));
+
+    match params.pid {
+        Some(pid) => match find_node(&tree, pid) {
+            Some(node) => HttpResponse::Ok().json(node),
+            None => HttpResponse::NotFound().json(format!("No process {}", pid)),
+        },
+        None => HttpResponse::Ok().json(tree),
+    }
+}
+
+fn find_node(nodes: &[ProcessNode], pid: u32) -> Option<&ProcessNode> {
+    nodes.iter().find_map(|node| {
+        if node.process.pid == pid {
+            Some(node)
+        } else {
+            find_node(&node.children, pid)
         }
-        
-        HttpResponse::Ok().json(metrics)
-    } else {
-        HttpResponse::InternalServerError().json("Failed to collect metrics")
+    })
+}
+
+// POST /api/processes/{pid}/actions with a `ProcessAction` body
+async fn post_process_action(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    pid: web::Path<u32>,
+    action: web::Json<ProcessAction>,
+) -> impl Responder {
+    let (pid, action) = (pid.into_inner(), action.into_inner());
+    let origin = format!("api {}", req.peer_addr().map_or("?".to_string(), |addr| addr.ip().to_string()));
+
+    let token = match &data.api_token {
+        Some(token) => token,
+        None => return HttpResponse::Forbidden().json("Process actions are disabled, set api.token in the config"),
+    };
+    if !bearer_matches(req.headers().get(AUTHORIZATION), token) {
+        warn!(target: AUDIT_TARGET, "{} {} pid {}: rejected, bad or missing token", origin, action, pid);
+        return HttpResponse::Unauthorized().json("Missing or invalid bearer token");
+    }
+
+    let name = data.sampler.latest()
+        .and_then(|m| m.processes.iter().find(|p| p.pid == pid).map(|p| p.name.clone()));
+    match perform_audited(pid, name.as_deref(), action, &origin) {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({ "pid": pid, "action": action, "ok": true })),
+        Err(MonitorError::ProcessAction(e)) => HttpResponse::BadRequest().json(e),
+        Err(MonitorError::Io(e)) if e.raw_os_error() == Some(libc::ESRCH) => {
+            HttpResponse::NotFound().json(format!("No process {}", pid))
+        }
+        Err(MonitorError::Io(e)) if e.raw_os_error() == Some(libc::EPERM) => {
+            HttpResponse::Forbidden().json(e.to_string())
+        }
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+// Compares the whole token whatever the mismatch, so response timing
+// does not reveal how much of a guess was right
+fn bearer_matches(header: Option<&HeaderValue>, token: &str) -> bool {
+    let given = match header.and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer ")) {
+        Some(given) => given.trim(),
+        None => return false,
+    };
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Deserialize)]
+struct RangeParams {
+    // Unix milliseconds; defaults to the last hour
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl RangeParams {
+    fn resolve(&self) -> (i64, i64) {
+        let end = self.end.unwrap_or_else(|| timestamp_ms(std::time::SystemTime::now()));
+        (self.start.unwrap_or(end - DEFAULT_RANGE_MS), end)
     }
 }
 
-async fn get_history(data: web::Data<Arc<Mutex<AppState>>>) -> impl Responder {
-    match data.lock() {
-        Ok(state) => HttpResponse::Ok().json(&state.metrics_history),
+#[derive(Deserialize)]
+struct SeriesParams {
+    metric: String,
+    start: Option<i64>,
+    end: Option<i64>,
+    bucket: Option<u64>,
+    agg: Option<String>,
+}
+
+// Read from SQLite on the blocking pool, with a read-only connection per
+// request; the recorder owns creating and migrating the database
+async fn with_storage<T, F>(config: StorageConfig, read: F) -> HttpResponse
+where
+    T: serde::Serialize + Send + 'static,
+    F: FnOnce(&MetricsStorage) -> rusqlite::Result<T> + Send + 'static,
+{
+    let result = web::block(move || {
+        let storage = MetricsStorage::open_read_only(&config.path)?;
+        read(&storage)
+    }).await;
+
+    match result {
+        Ok(Ok(value)) => HttpResponse::Ok().json(value),
+        Ok(Err(e)) => {
+            error!("Storage query failed: {}", e);
+            HttpResponse::InternalServerError().json("Storage query failed")
+        }
         Err(e) => {
-            println!("Failed to lock state: {:?}", e);
+            error!("Storage task failed: {}", e);
             HttpResponse::InternalServerError().json("Internal server error")
         }
     }
 }
 
-pub async fn start_server() -> std::io::Result<()> {
-    let state = web::Data::new(Arc::new(Mutex::new(AppState::default())));
+async fn get_series(data: web::Data<AppState>, params: web::Query<SeriesParams>) -> impl Responder {
+    let metric = match params.metric.parse::<Metric>() {
+        Ok(metric) => metric,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    let aggregation = match params.agg.as_deref().unwrap_or("avg").parse::<Aggregation>() {
+        Ok(aggregation) => aggregation,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    let bucket_secs = params.bucket.unwrap_or(DEFAULT_BUCKET_SECS);
+    if bucket_secs > MAX_BUCKET_SECS {
+        return HttpResponse::BadRequest().json(format!("bucket must be at most {} seconds", MAX_BUCKET_SECS));
+    }
+    let (start_ms, end_ms) = RangeParams { start: params.start, end: params.end }.resolve();
+    let query = SeriesQuery {
+        metric,
+        start_ms,
+        end_ms,
+        bucket_secs,
+        aggregation,
+    };
+    with_storage(data.storage.clone(), move |storage| storage.query_series(&query)).await
+}
+
+// OOM kills and memory limit events from storage, oldest first
+async fn get_events(data: web::Data<AppState>, params: web::Query<RangeParams>) -> impl Responder {
+    let (start_ms, end_ms) = params.resolve();
+    with_storage(data.storage.clone(), move |storage| storage.events(start_ms, end_ms)).await
+}
+
+async fn get_series_metrics(data: web::Data<AppState>, params: web::Query<RangeParams>) -> impl Responder {
+    let (start_ms, end_ms) = params.resolve();
+    with_storage(data.storage.clone(), move |storage| storage.list_metrics(start_ms, end_ms)).await
+}
+
+pub async fn start_server(sampler: SamplerHandle, config: &Config) -> std::io::Result<()> {
+    let state = web::Data::new(
+        AppState::new(sampler, config.storage.clone()).with_api_token(config.api.token.clone()),
+    );
 
     println!("Starting server at http://localhost:8080");
-    
+
     HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
@@ -67,9 +328,34 @@ pub async fn start_server() -> std::io::Result<()> {
                 web::scope("/api")
                     .route("/metrics", web::get().to(get_metrics))
                     .route("/history", web::get().to(get_history))
+                    .route("/series", web::get().to(get_series))
+                    .route("/series/metrics", web::get().to(get_series_metrics))
+                    .route("/events", web::get().to(get_events))
+                    .route("/connections", web::get().to(get_connections))
+                    .route("/cgroups", web::get().to(get_cgroups))
+                    .route("/leaks", web::get().to(get_leaks))
+                    .route("/processes/tree", web::get().to(get_process_tree))
+                    .route("/processes/{pid}", web::get().to(get_process))
+                    .route("/processes/{pid}/actions", web::post().to(post_process_action))
             )
+            .route("/metrics", web::get().to(get_prometheus_metrics))
             .service(fs::Files::new("/", "./static").index_file("index.html"))
     }).bind("127.0.0.1:8080")?
     .run()
     .await
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token_check() {
+        let header = |value: &'static str| HeaderValue::from_static(value);
+        assert!(bearer_matches(Some(&header("Bearer s3cret")), "s3cret"));
+        assert!(!bearer_matches(Some(&header("Bearer s3cre")), "s3cret"));
+        assert!(!bearer_matches(Some(&header("Bearer s3creT")), "s3cret"));
+        assert!(!bearer_matches(Some(&header("Basic s3cret")), "s3cret"));
+        assert!(!bearer_matches(None, "s3cret"));
+    }
+}
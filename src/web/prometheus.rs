@@ -0,0 +1,467 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::time::UNIX_EPOCH;
+use crate::monitor::{DetailedMetrics, DiskMetrics, InterfaceMetrics};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Only the biggest processes get their own series, so a fork bomb
+// cannot blow up the scraper's label cardinality.
+const TOP_PROCESSES: usize = 10;
+// Likewise the largest workload cgroups, one per container on a busy node
+const TOP_CGROUPS: usize = 20;
+
+// Metric name, help and the field it reads
+type InterfaceCounter = (&'static str, &'static str, fn(&InterfaceMetrics) -> u64);
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetricType {
+    Gauge,
+    Counter,
+}
+
+impl MetricType {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricType::Gauge => "gauge",
+            MetricType::Counter => "counter",
+        }
+    }
+}
+
+// Text exposition format writer (HELP/TYPE header followed by samples)
+#[derive(Default)]
+pub struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn family(&mut self, name: &str, kind: MetricType, help: &str) -> &mut Self {
+        let _ = writeln!(self.out, "# HELP {} {}", name, escape_help(help));
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind.as_str());
+        self
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (key, val)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{}=\"{}\"", key, escape_label(val));
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", format_value(value));
+        self
+    }
+
+    // Single unlabelled sample with its header
+    pub fn single(&mut self, name: &str, kind: MetricType, help: &str, value: f64) -> &mut Self {
+        self.family(name, kind, help).sample(name, &[], value)
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// One filesystem series per mount, labelled like node_exporter's
+fn filesystem_labels(disk: &DiskMetrics) -> [(&str, &str); 3] {
+    [("device", &disk.name), ("fstype", &disk.file_system), ("mountpoint", &disk.mount_point)]
+}
+
+pub fn render(metrics: &DetailedMetrics) -> String {
+    use MetricType::{Counter, Gauge};
+    let mut exp = Exposition::new();
+    let basic = &metrics.basic;
+
+    exp.single("memory_monitor_cpu_usage_percent", Gauge,
+        "Global CPU utilization in percent.", basic.cpu_usage as f64);
+    // node_exporter labels CPUs by index ("0", not "cpu0")
+    let cores: Vec<_> = metrics.cpu.cores.iter()
+        .map(|core| (core.name.strip_prefix("cpu").unwrap_or(&core.name), core))
+        .collect();
+    exp.family("memory_monitor_cpu_core_usage_percent", Gauge, "CPU utilization per core in percent.");
+    for (cpu, core) in &cores {
+        exp.sample("memory_monitor_cpu_core_usage_percent", &[("cpu", cpu)], core.usage as f64);
+    }
+    exp.family("node_cpu_scaling_frequency_hertz", Gauge, "Current scaled CPU thread frequency in hertz.");
+    // Zero means the frequency is unknown (VMs often do not expose it)
+    for (cpu, core) in cores.iter().filter(|(_, core)| core.frequency_mhz > 0) {
+        exp.sample("node_cpu_scaling_frequency_hertz", &[("cpu", cpu)], core.frequency_mhz as f64 * 1e6);
+    }
+    let b = &metrics.cpu.breakdown;
+    let modes = [
+        ("user", b.user), ("nice", b.nice), ("system", b.system), ("idle", b.idle),
+        ("iowait", b.iowait), ("irq", b.irq), ("softirq", b.softirq), ("steal", b.steal),
+    ];
+    exp.family("memory_monitor_cpu_mode_percent", Gauge,
+        "Share of CPU time spent in each mode over the last interval.");
+    for (mode, value) in modes {
+        exp.sample("memory_monitor_cpu_mode_percent", &[("mode", mode)], value);
+    }
+
+    exp.single("node_memory_MemTotal_bytes", Gauge,
+        "Total physical memory in bytes.", basic.total_memory as f64);
+    exp.single("node_memory_MemAvailable_bytes", Gauge,
+        "Memory available for new workloads in bytes.", basic.available_memory as f64);
+    exp.single("memory_monitor_memory_used_bytes", Gauge,
+        "Memory in use in bytes.", basic.used_memory as f64);
+    // Named after the /proc/meminfo fields, as node_exporter does
+    let memory = &metrics.memory;
+    let meminfo = [
+        ("node_memory_SwapTotal_bytes", "Swap space in bytes.", memory.swap_total),
+        ("node_memory_SwapFree_bytes", "Unused swap space in bytes.",
+            memory.swap_total.saturating_sub(memory.swap_used)),
+        ("node_memory_SwapCached_bytes", "Swapped-out memory still in the page cache in bytes.", memory.swap_cached),
+        ("node_memory_Buffers_bytes", "Block device buffers in bytes.", memory.buffers),
+        ("node_memory_Cached_bytes", "Page cache in bytes.", memory.cached),
+        ("node_memory_Shmem_bytes", "Shared memory and tmpfs in bytes.", memory.shared),
+        ("node_memory_Slab_bytes", "Kernel slab allocations in bytes.", memory.slab),
+        ("node_memory_SReclaimable_bytes", "Reclaimable part of the slab in bytes.", memory.slab_reclaimable),
+        ("node_memory_Dirty_bytes", "Memory waiting to be written back in bytes.", memory.dirty),
+        ("node_memory_Writeback_bytes", "Memory being written back in bytes.", memory.writeback),
+        ("node_memory_Committed_AS_bytes", "Memory committed to allocations in bytes.", memory.committed_as),
+        ("node_memory_CommitLimit_bytes", "Commit limit under strict overcommit in bytes.", memory.commit_limit),
+        ("node_memory_HugePages_Total", "Huge pages in the pool.", memory.hugepages_total),
+        ("node_memory_HugePages_Free", "Huge pages not yet allocated.", memory.hugepages_free),
+        ("node_memory_Hugepagesize_bytes", "Huge page size in bytes.", memory.hugepage_size),
+    ];
+    for (name, help, value) in meminfo {
+        exp.single(name, Gauge, help, value as f64);
+    }
+    exp.single("node_vmstat_pswpin", Counter, "Pages swapped in.", memory.swap_in_pages as f64);
+    exp.single("node_vmstat_pswpout", Counter, "Pages swapped out.", memory.swap_out_pages as f64);
+    exp.single("node_vmstat_pgmajfault", Counter, "Major page faults.", memory.major_faults as f64);
+    exp.single("node_vmstat_oom_kill", Counter, "Processes killed by the OOM killer.", memory.oom_kills as f64);
+    exp.single("memory_monitor_swap_in_pages_per_second", Gauge,
+        "Swap-in rate over the last interval.", memory.swap_in_pages_per_sec);
+    exp.single("memory_monitor_swap_out_pages_per_second", Gauge,
+        "Swap-out rate over the last interval.", memory.swap_out_pages_per_sec);
+    exp.single("memory_monitor_major_faults_per_second", Gauge,
+        "Major page fault rate over the last interval.", memory.major_faults_per_sec);
+    // node_exporter's names for the cumulative stall times
+    let psi = &metrics.pressure;
+    let resources: Vec<_> = [("cpu", psi.cpu), ("memory", psi.memory), ("io", psi.io)]
+        .into_iter()
+        .filter_map(|(resource, pressure)| pressure.map(|p| (resource, p)))
+        .collect();
+    for (resource, pressure) in &resources {
+        exp.single(&format!("node_pressure_{}_waiting_seconds_total", resource), Counter,
+            &format!("Total time in seconds that processes have waited for {}.", resource),
+            pressure.some.total as f64 / 1e6);
+        if *resource != "cpu" {
+            exp.single(&format!("node_pressure_{}_stalled_seconds_total", resource), Counter,
+                &format!("Total time in seconds no process could make progress due to {}.", resource),
+                pressure.full.total as f64 / 1e6);
+        }
+    }
+    exp.family("memory_monitor_pressure_percent", Gauge,
+        "Share of time tasks were stalled, averaged over the window.");
+    for (resource, pressure) in &resources {
+        for (kind, line) in [("some", pressure.some), ("full", pressure.full)] {
+            for (window, value) in [("10s", line.avg10), ("60s", line.avg60), ("300s", line.avg300)] {
+                exp.sample("memory_monitor_pressure_percent",
+                    &[("resource", resource), ("kind", kind), ("window", window)], value);
+            }
+        }
+    }
+
+    if let Some(cg) = &metrics.cgroup {
+        exp.single("memory_monitor_cgroup_memory_current_bytes", Gauge,
+            "Memory charged to the monitor's cgroup in bytes.", cg.memory_current as f64);
+        exp.single("memory_monitor_cgroup_memory_working_set_bytes", Gauge,
+            "Cgroup memory minus inactive page cache in bytes.", cg.memory_working_set as f64);
+        if let Some(max) = cg.memory_max {
+            exp.single("memory_monitor_cgroup_memory_max_bytes", Gauge, "Cgroup memory.max in bytes.", max as f64);
+        }
+        exp.single("memory_monitor_cgroup_memory_max_events_total", Counter,
+            "Times the cgroup hit memory.max.", cg.memory_max_events as f64);
+        exp.single("memory_monitor_cgroup_oom_kills_total", Counter,
+            "Processes in the cgroup killed by the OOM killer.", cg.oom_kill_events as f64);
+        if let Some(cores) = cg.cpu_quota_cores {
+            exp.single("memory_monitor_cgroup_cpu_quota_cores", Gauge, "Cgroup cpu.max quota in CPUs.", cores);
+        }
+        exp.single("memory_monitor_cgroup_cpu_usage_seconds_total", Counter,
+            "CPU time used by the cgroup.", cg.cpu_usage_usec as f64 / 1e6);
+        exp.single("memory_monitor_cgroup_cpu_throttled_periods_total", Counter,
+            "Enforcement periods in which the cgroup was throttled.", cg.cpu_nr_throttled as f64);
+        exp.single("memory_monitor_cgroup_cpu_throttled_seconds_total", Counter,
+            "Time the cgroup spent throttled.", cg.cpu_throttled_usec as f64 / 1e6);
+    }
+
+    exp.single("node_load1", Gauge, "1m load average.", basic.load_average.one);
+    exp.single("node_load5", Gauge, "5m load average.", basic.load_average.five);
+    exp.single("node_load15", Gauge, "15m load average.", basic.load_average.fifteen);
+
+    exp.family("node_filesystem_size_bytes", Gauge, "Filesystem size in bytes.");
+    for disk in &metrics.disks {
+        exp.sample("node_filesystem_size_bytes", &filesystem_labels(disk), disk.total_space as f64);
+    }
+    exp.family("node_filesystem_avail_bytes", Gauge, "Filesystem space available in bytes.");
+    for disk in &metrics.disks {
+        exp.sample("node_filesystem_avail_bytes", &filesystem_labels(disk), disk.available_space as f64);
+    }
+    // I/O is per block device, which may be mounted more than once.
+    // node_exporter labels block devices by kernel name, without /dev/
+    let mut seen = HashSet::new();
+    let devices: Vec<_> = metrics.disks.iter()
+        .filter(|disk| seen.insert(disk.name.as_str()))
+        .map(|disk| (disk.name.strip_prefix("/dev/").unwrap_or(&disk.name), disk))
+        .collect();
+    exp.family("node_disk_read_bytes_total", Counter, "The total number of bytes read successfully.");
+    for (device, disk) in &devices {
+        exp.sample("node_disk_read_bytes_total", &[("device", device)], disk.read_bytes as f64);
+    }
+    exp.family("node_disk_written_bytes_total", Counter, "The total number of bytes written successfully.");
+    for (device, disk) in &devices {
+        exp.sample("node_disk_written_bytes_total", &[("device", device)], disk.write_bytes as f64);
+    }
+    exp.family("memory_monitor_disk_read_bytes_per_second", Gauge, "Disk read rate over the last interval.");
+    for (device, disk) in &devices {
+        exp.sample("memory_monitor_disk_read_bytes_per_second", &[("device", device)], disk.read_bytes_per_sec);
+    }
+    exp.family("memory_monitor_disk_written_bytes_per_second", Gauge, "Disk write rate over the last interval.");
+    for (device, disk) in &devices {
+        exp.sample("memory_monitor_disk_written_bytes_per_second", &[("device", device)], disk.write_bytes_per_sec);
+    }
+
+    // No totals over all interfaces: the sum drops whenever an interface
+    // goes away, which a counter must not. Sum node_network_* instead.
+    exp.single("memory_monitor_network_connections", Gauge,
+        "Established TCP connections.", metrics.network.connections as f64);
+    exp.family("memory_monitor_tcp_sockets", Gauge, "TCP sockets (IPv4 and IPv6) by state.");
+    for (state, count) in &metrics.sockets.tcp_states {
+        exp.sample("memory_monitor_tcp_sockets", &[("state", state)], *count as f64);
+    }
+    exp.single("memory_monitor_udp_sockets", Gauge,
+        "UDP sockets (IPv4 and IPv6).", metrics.sockets.udp_sockets as f64);
+
+    let interfaces = &metrics.network.interfaces;
+    let counters: [InterfaceCounter; 8] = [
+        ("node_network_receive_bytes_total", "Network device statistic receive_bytes.", |i| i.rx_bytes),
+        ("node_network_transmit_bytes_total", "Network device statistic transmit_bytes.", |i| i.tx_bytes),
+        ("node_network_receive_packets_total", "Network device statistic receive_packets.", |i| i.rx_packets),
+        ("node_network_transmit_packets_total", "Network device statistic transmit_packets.", |i| i.tx_packets),
+        ("node_network_receive_errs_total", "Network device statistic receive_errs.", |i| i.rx_errors),
+        ("node_network_transmit_errs_total", "Network device statistic transmit_errs.", |i| i.tx_errors),
+        ("node_network_receive_drop_total", "Network device statistic receive_drop.", |i| i.rx_dropped),
+        ("node_network_transmit_drop_total", "Network device statistic transmit_drop.", |i| i.tx_dropped),
+    ];
+    for (name, help, value) in counters {
+        exp.family(name, Counter, help);
+        for interface in interfaces {
+            exp.sample(name, &[("device", &interface.name)], value(interface) as f64);
+        }
+    }
+    exp.family("memory_monitor_network_receive_bytes_per_second", Gauge, "Receive rate over the last interval.");
+    for interface in interfaces {
+        exp.sample("memory_monitor_network_receive_bytes_per_second",
+            &[("device", &interface.name)], interface.rx_bytes_per_sec);
+    }
+    exp.family("memory_monitor_network_transmit_bytes_per_second", Gauge, "Transmit rate over the last interval.");
+    for interface in interfaces {
+        exp.sample("memory_monitor_network_transmit_bytes_per_second",
+            &[("device", &interface.name)], interface.tx_bytes_per_sec);
+    }
+
+    exp.family("node_hwmon_temp_celsius", Gauge, "Hardware monitor for temperature.");
+    for temp in &metrics.temperatures {
+        exp.sample("node_hwmon_temp_celsius", &[("sensor", &temp.label)], temp.value as f64);
+    }
+
+    // Workload cgroups only: slices would repeat their children's usage
+    let mut workloads: Vec<_> = metrics.cgroups.iter().filter(|cg| cg.kind.is_workload()).collect();
+    workloads.sort_by_key(|cg| std::cmp::Reverse(cg.memory_current));
+    workloads.truncate(TOP_CGROUPS);
+    exp.family("memory_monitor_cgroup_usage_memory_bytes", Gauge, "Memory charged to the largest workload cgroups.");
+    for cg in &workloads {
+        exp.sample("memory_monitor_cgroup_usage_memory_bytes",
+            &[("cgroup", &cg.path), ("name", &cg.name)], cg.memory_current as f64);
+    }
+    exp.family("memory_monitor_cgroup_usage_cpu_percent", Gauge, "CPU usage of the largest workload cgroups, in percent of one CPU.");
+    for cg in &workloads {
+        exp.sample("memory_monitor_cgroup_usage_cpu_percent",
+            &[("cgroup", &cg.path), ("name", &cg.name)], cg.cpu_usage);
+    }
+
+    // Already sorted fastest-growing first
+    exp.family("memory_monitor_process_leak_growth_bytes_per_hour", Gauge,
+        "Memory growth of the fastest-growing processes suspected of leaking.");
+    for leak in metrics.leaks.iter().take(TOP_PROCESSES) {
+        exp.sample("memory_monitor_process_leak_growth_bytes_per_hour",
+            &[("pid", &leak.pid.to_string()), ("name", &leak.name)], leak.growth_bytes_per_hour);
+    }
+
+    exp.single("memory_monitor_processes", Gauge,
+        "Number of processes.", metrics.processes.len() as f64);
+    let mut top: Vec<_> = metrics.processes.iter().collect();
+    top.sort_by_key(|p| std::cmp::Reverse(p.memory));
+    top.truncate(TOP_PROCESSES);
+    let top: Vec<_> = top.into_iter().map(|p| (p.pid.to_string(), p)).collect();
+    exp.family("memory_monitor_process_resident_memory_bytes", Gauge,
+        "Resident memory of the largest processes.");
+    for (pid, process) in &top {
+        exp.sample("memory_monitor_process_resident_memory_bytes",
+            &[("pid", pid), ("name", &process.name)], process.memory as f64);
+    }
+    exp.family("memory_monitor_process_cpu_usage_percent", Gauge,
+        "CPU usage of the largest processes.");
+    for (pid, process) in &top {
+        exp.sample("memory_monitor_process_cpu_usage_percent",
+            &[("pid", pid), ("name", &process.name)], process.cpu_usage as f64);
+    }
+
+    if !metrics.gauges.is_empty() {
+        exp.family("memory_monitor_gauge", Gauge, "Gauges reported by custom collectors.");
+        for (name, value) in &metrics.gauges {
+            exp.sample("memory_monitor_gauge", &[("name", name)], *value);
+        }
+    }
+
+    let timestamp = metrics.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    exp.single("memory_monitor_last_sample_timestamp_seconds", Gauge,
+        "Unix time of the sample being exposed.", timestamp.as_secs_f64());
+
+    exp.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::{CgroupUsage, CoreMetrics, LeakSuspect, ProcessMetrics};
+    use crate::procfs::cgroup::CgroupKind;
+
+    #[test]
+    fn test_label_escaping() {
+        let mut exp = Exposition::new();
+        exp.family("x", MetricType::Gauge, "line\nbreak")
+            .sample("x", &[("name", "a\"b\\c")], f64::NAN);
+        assert_eq!(exp.finish(), "# HELP x line\\nbreak\n# TYPE x gauge\nx{name=\"a\\\"b\\\\c\"} NaN\n");
+    }
+
+    #[test]
+    fn test_render_bounds_processes() {
+        let mut metrics = DetailedMetrics::default();
+        let disk = DiskMetrics {
+            name: "/dev/sda1".to_string(),
+            mount_point: "/".to_string(),
+            file_system: "ext4".to_string(),
+            total_space: 100,
+            available_space: 40,
+            read_bytes: 512,
+            write_bytes: 1024,
+            read_bytes_per_sec: 0.0,
+            write_bytes_per_sec: 0.0,
+        };
+        // The same device bind-mounted elsewhere
+        metrics.disks.push(DiskMetrics { mount_point: "/var/lib/docker".to_string(), ..disk.clone() });
+        metrics.disks.push(disk);
+        metrics.processes = (0..50).map(|pid| ProcessMetrics {
+            pid,
+            name: format!("proc{}", pid),
+            cpu_usage: 0.0,
+            memory: pid as u64,
+            ..ProcessMetrics::default()
+        }).collect();
+
+        metrics.cpu.cores.push(CoreMetrics { name: "cpu3".to_string(), usage: 50.0, frequency_mhz: 2400 });
+        metrics.cpu.breakdown.steal = 12.5;
+        metrics.memory.swap_total = 4096;
+        metrics.memory.swap_used = 1024;
+        metrics.network.interfaces.push(InterfaceMetrics {
+            name: "eth0".to_string(),
+            rx_dropped: 17,
+            ..InterfaceMetrics::default()
+        });
+
+        let text = render(&metrics);
+        assert!(text.contains("node_cpu_scaling_frequency_hertz{cpu=\"3\"} 2400000000\n"));
+        assert!(text.contains("memory_monitor_cpu_mode_percent{mode=\"steal\"} 12.5\n"));
+        assert!(text.contains("node_memory_SwapFree_bytes 3072\n"));
+        assert!(text.contains("node_network_receive_drop_total{device=\"eth0\"} 17\n"));
+        assert!(text.contains("# TYPE node_disk_read_bytes_total counter\n"));
+        assert_eq!(text.matches("node_disk_read_bytes_total{device=\"sda1\"} 512\n").count(), 1);
+        assert!(text.contains("node_filesystem_avail_bytes{device=\"/dev/sda1\",fstype=\"ext4\",mountpoint=\"/\"} 40\n"));
+        assert!(text.contains("node_filesystem_avail_bytes{device=\"/dev/sda1\",fstype=\"ext4\",mountpoint=\"/var/lib/docker\"} 40\n"));
+        assert!(text.contains("name=\"proc49\""));
+        assert!(!text.contains("name=\"proc39\""));
+        assert_eq!(text.matches("memory_monitor_process_resident_memory_bytes{").count(), TOP_PROCESSES);
+    }
+
+    #[test]
+    fn test_render_bounds_leaks() {
+        let mut metrics = DetailedMetrics::default();
+        metrics.leaks = (0..50).rev().map(|pid| LeakSuspect {
+            pid,
+            name: format!("leaky{}", pid),
+            start_time: 0,
+            memory: 4096,
+            growth_bytes_per_hour: pid as f64,
+            r_squared: 0.99,
+            monotonic_ratio: 1.0,
+            growth_bytes: 2048,
+            observed_secs: 1800,
+            samples: 60,
+        }).collect();
+
+        let text = render(&metrics);
+        assert_eq!(text.matches("memory_monitor_process_leak_growth_bytes_per_hour{").count(), TOP_PROCESSES);
+        assert!(text.contains("name=\"leaky49\""));
+        assert!(!text.contains("name=\"leaky39\""));
+    }
+
+    #[test]
+    fn test_render_bounds_cgroups() {
+        let mut metrics = DetailedMetrics::default();
+        metrics.cgroups = (0..50).map(|i| CgroupUsage {
+            path: format!("/kubepods.slice/pod{}", i),
+            kind: if i == 0 { CgroupKind::Slice } else { CgroupKind::Pod },
+            name: format!("pod{}", i),
+            memory_current: i * 1024,
+            memory_max: None,
+            cpu_usage: 0.0,
+            io_read_bytes: 0,
+            io_write_bytes: 0,
+            io_read_bytes_per_sec: 0.0,
+            io_write_bytes_per_sec: 0.0,
+            pids: Vec::new(),
+            memory_high_events: 0,
+            memory_max_events: 0,
+            oom_kill_events: 0,
+        }).collect();
+        // The biggest of all is a slice, which is never exported
+        metrics.cgroups[0].memory_current = u64::MAX;
+
+        let text = render(&metrics);
+        assert_eq!(text.matches("memory_monitor_cgroup_usage_memory_bytes{").count(), TOP_CGROUPS);
+        assert!(text.contains("name=\"pod49\""));
+        assert!(!text.contains("name=\"pod29\""));
+        assert!(!text.contains("name=\"pod0\""));
+    }
+}